@@ -48,8 +48,8 @@ fn send_1000_messages(b: &mut Bencher) {
     let tx = Arc::new(Mutex::new(tx));
 
     let props = Props::new(Arc::new(InternalState::new), tx);
-    let actor_ref_1 = actor_system.actor_of(props.clone(), "sender".to_owned());
-    let actor_ref_2 = actor_system.actor_of(props.clone(), "receiver".to_owned());
+    let actor_ref_1 = actor_system.actor_of(props.clone(), "sender".to_owned()).unwrap();
+    let actor_ref_2 = actor_system.actor_of(props.clone(), "receiver".to_owned()).unwrap();
 
     b.iter(|| {
         for _ in 0..999 {
@@ -83,9 +83,12 @@ fn create_1000_actors(b: &mut Bencher) {
 
     let props = Props::new(Arc::new(Dummy::new), ());
 
+    // The names have to be unique across iterations, as names of living actors can't be reused.
+    let mut created = 0;
     b.iter(|| {
-        for i in 0..1_000 {
-            actor_system.actor_of(props.clone(), format!("{}", i));
+        for _ in 0..1_000 {
+            actor_system.actor_of(props.clone(), format!("{}", created)).unwrap();
+            created += 1;
         }
     });
 
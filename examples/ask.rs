@@ -2,7 +2,7 @@
 /// The ask pattern consists of sending a message expecting an answer, the answer is then put in
 /// a Future.
 /// We chose to use it from the "outside" here, but it works as well inside an actor by calling
-/// `context.ask(target, message)`.
+/// `context.ask_anonymous(target, message)`.
 
 extern crate env_logger;
 extern crate rand;
@@ -48,9 +48,9 @@ fn main() {
     actor_system.spawn_threads(1);
 
     let props = Props::new(Arc::new(Answerer::new), ());
-    let answerer = actor_system.actor_of(props, "answerer".to_owned());
+    let answerer = actor_system.actor_of(props, "answerer".to_owned()).unwrap();
 
-    let future = actor_system.ask(answerer, Exchanges::Request, "request".to_owned());
+    let future = actor_system.ask_anonymous(answerer, Exchanges::Request);
     let x: Exchanges = actor_system.extract_result(future);
 
     match x {
@@ -30,8 +30,8 @@ fn main() {
     let actor_system = ActorSystem::new("counter".to_owned());
 
     let props = Props::new(Arc::new(Counter::new),());
-    let actor_ref_1 = actor_system.actor_of(props.clone(), "counter".to_owned());
-    let actor_ref_2 = actor_system.actor_of(props.clone(), "sender".to_owned());
+    let actor_ref_1 = actor_system.actor_of(props.clone(), "counter".to_owned()).unwrap();
+    let actor_ref_2 = actor_system.actor_of(props.clone(), "sender".to_owned()).unwrap();
 
     actor_ref_1.tell_to(actor_ref_2.clone(), ());
     actor_ref_1.tell_to(actor_ref_2.clone(), ());
@@ -66,7 +66,7 @@ fn main() {
     let actor_system = ActorSystem::new("test".to_owned());
 
     let props = Props::new(Arc::new(HelloWorld::new), ());
-    let _actor = actor_system.actor_of(props, "hello_world".to_owned());
+    let _actor = actor_system.actor_of(props, "hello_world".to_owned()).unwrap();
 
     std::thread::sleep(Duration::from_millis(100));
     actor_system.shutdown();
@@ -3,15 +3,19 @@
 /// It is used to handle messages, system messages, termination, initialization, restarting and
 /// creation of actors.
 
-use std::any::Any;
+use std::any::{Any, TypeId};
 use std::collections::{HashMap, VecDeque};
 use std::mem;
 use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::thread;
+use std::time::Duration;
 
-use actors::{Actor, ActorPath, ActorRef, ActorSystem, Message, Props};
-use actors::future::{Computation, Complete, Future, FutureState};
-use actors::name_resolver::ResolveRequest;
+use actors::{Actor, ActorFailed, ActorPath, ActorRef, ActorStarted, ActorStopped, ActorSystem, DeadLetter, Delivery,
+             Message, Props, RobotsError};
+use actors::future::{complete_future, forward_to_future, Computation, Complete, Future, FutureFailure, FutureState,
+                     FutureTimeout, Gather, GatherFinisher};
 use actors::props::ActorFactory;
+use persistence::MailboxStorage;
 
 /// Closure to handle failure of an Actor.
 pub type FailureHandler = Arc<Fn(Failure, ActorCell) + Send + Sync>;
@@ -69,23 +73,40 @@ impl ActorCell {
     }
 
     /// Puts a message with its sender in the Actor's mailbox and schedules the Actor.
-    pub fn receive_message(&self, message: InnerMessage, sender: ActorRef) {
+    ///
+    /// The message is a dead letter if the actor is not the incarnation with the given uid.
+    pub fn receive_message(&self, message: InnerMessage, sender: ActorRef, uid: u64) -> Delivery {
         let inner = unwrap_inner!(self.inner_cell, {
             warn!("A message was send to a ref to a stopped actor");
-            return;
+            return Delivery::DeadLetter;
         });
-        inner.receive_message(message, sender);
+        if inner.uid != uid {
+            warn!("A message was send to a previous incarnation of {}", inner.path.logical_path());
+            return Delivery::DeadLetter;
+        }
+        if let Err(error) = inner.receive_message(message, sender) {
+            warn!("A message to {} could not be stored: {}", inner.path.logical_path(), error);
+            return Delivery::DeadLetter;
+        }
         inner.system.enqueue_actor(self.actor_ref());
+        Delivery::Delivered
     }
 
     /// Puts a system message with its sender in the Actor's system mailbox and schedules the Actor.
-    pub fn receive_system_message(&self, system_message: SystemMessage) {
+    ///
+    /// The message is a dead letter if the actor is not the incarnation with the given uid.
+    pub fn receive_system_message(&self, system_message: SystemMessage, uid: u64) -> Delivery {
         let inner = unwrap_inner!(self.inner_cell, {
             warn!("A message was send to a ref to a stopped actor");
-            return;
+            return Delivery::DeadLetter;
         });
+        if inner.uid != uid {
+            warn!("A system message was send to a previous incarnation of {}", inner.path.logical_path());
+            return Delivery::DeadLetter;
+        }
         inner.receive_system_message(system_message);
         inner.system.enqueue_actor(self.actor_ref());
+        Delivery::Delivered
     }
 
     /// Makes the Actor handle an envelope in its mailbox.
@@ -96,6 +117,163 @@ impl ActorCell {
         });
         inner.handle_envelope(self.clone());
     }
+
+    /// Creates a Future as an anonymous child of the actor.
+    fn new_future(&self) -> ActorRef {
+        self.actor_of_anonymous(Props::new(Arc::new(Future::new), ()))
+    }
+
+    /// Creates a Future child with the given name, if it can't be created the error is logged and
+    /// the ActorRef given sends every message to the dead letters.
+    fn named_future(&self, name: String) -> ActorRef {
+        match self.actor_of(Props::new(Arc::new(Future::new), ()), name) {
+            Ok(future) => future,
+            Err(error) => {
+                error!("{} could not create a Future: {}", self.path().logical_path(), error);
+                ActorRef::dead_letters()
+            },
+        }
+    }
+
+    /// Completes the future with the actor with the given logical path, see `identify_actor`.
+    fn identify(&self, name: String, future: ActorRef) -> ActorRef {
+        let inner = unwrap_inner!(self.inner_cell, {
+            panic!("Tried to get the actor system of a no longer existing actor while resolving \
+                    a path. This should *never* happen");
+        });
+        let path = inner.path.resolve(&name);
+        self.complete(future.clone(), inner.system.lookup(&path));
+        future
+    }
+
+    /// Creates a Gather actor completing the `result` Future with the results of the `futures`
+    /// once they are all completed.
+    fn gather(&self, futures: Vec<ActorRef>, result: ActorRef, finish: GatherFinisher) {
+        let gather = self.actor_of_anonymous(Props::new(Arc::new(Gather::new), (futures.len(), result, finish)));
+        for (index, future) in futures.into_iter().enumerate() {
+            self.tell(future, Gather::forward_item(gather.clone(), index));
+        }
+    }
+
+    /// Unique id of this incarnation of the actor, if it is still alive.
+    ///
+    /// A restarted actor keeps its uid, but an actor created with the path of a previous one gets a
+    /// new uid.
+    pub fn uid(&self) -> Option<u64> {
+        let inner = unwrap_inner!(self.inner_cell, {
+            return None;
+        });
+        Some(inner.uid)
+    }
+
+    /// Number of messages waiting in the mailbox of the actor.
+    pub fn mailbox_len(&self) -> usize {
+        let inner = unwrap_inner!(self.inner_cell, {
+            return 0;
+        });
+        inner.mailbox_len()
+    }
+
+    /// Gives the child of the actor with the given name, if any.
+    pub fn child(&self, name: &str) -> Option<ActorRef> {
+        let inner = unwrap_inner!(self.inner_cell, {
+            return None;
+        });
+        let path = inner.path.child(name.to_owned());
+        let children = inner.children.lock().unwrap();
+        children.get(&path).cloned()
+    }
+
+    /// Gives the actor with the given path, resolved from the path of this actor, if it exists.
+    ///
+    /// This is the synchronous version of `identify_actor`.
+    pub(crate) fn lookup(&self, path: &str) -> Option<ActorRef> {
+        let inner = unwrap_inner!(self.inner_cell, {
+            return None;
+        });
+        inner.system.lookup(&inner.path.resolve(path))
+    }
+
+    /// Spawns a child actor with a generated unique name, giving the error if it can't be created.
+    pub(crate) fn try_actor_of_anonymous(&self, props: Arc<ActorFactory>) -> Result<ActorRef, RobotsError> {
+        let inner = unwrap_inner!(self.inner_cell, {
+            panic!("Tried to create an actor from the context of a no longer existing actor");
+        });
+        let name = inner.anonymous_name();
+        self.spawn_child(&inner, props, name)
+    }
+
+    /// Runs the task on the scheduler of the system once the delay has passed.
+    pub(crate) fn schedule(&self, delay: Duration, task: Box<Fn() + Send>) {
+        let inner = unwrap_inner!(self.inner_cell, {
+            return;
+        });
+        inner.system.schedule(delay, task);
+    }
+
+    /// Publishes a DeadLetter on the EventStream if a message sent by the actor was not delivered.
+    fn check_delivery(&self, recipient: &ActorRef, delivery: Delivery) {
+        if delivery != Delivery::DeadLetter {
+            return;
+        }
+        let inner = unwrap_inner!(self.inner_cell, {
+            return;
+        });
+        inner.system.event_stream().publish(DeadLetter {
+            recipient: recipient.clone(),
+            sender: self.actor_ref(),
+        });
+    }
+
+    /// Tells whether the actor has not been dropped yet.
+    pub fn is_alive(&self) -> bool {
+        match self.inner_cell {
+            Ref::StrongRef(_) => true,
+            Ref::WeakRef(ref inner) => inner.upgrade().is_some(),
+        }
+    }
+
+    /// Creates a child actor with the given name, which is expected to be valid.
+    ///
+    /// This fails if the name is already taken by another child, even if it is terminating.
+    fn spawn_child(&self, inner: &InnerActorCell, props: Arc<ActorFactory>, name: String) -> Result<ActorRef, RobotsError> {
+        let path = self.path().child(name);
+        let external_ref = {
+            // The lock is kept until the child is inserted, so that no other child can take its
+            // name in the meantime.
+            let mut children = inner.children.lock().unwrap();
+            if children.contains_key(&path) {
+                return Err(RobotsError::NameTaken(path.logical_path().clone()));
+            }
+            {
+                let mut terminating_children = inner.terminating_children.lock().unwrap();
+                if terminating_children.get(&path).map_or(false, |child| child.is_alive()) {
+                    return Err(RobotsError::NameTaken(path.logical_path().clone()));
+                }
+                terminating_children.remove(&path);
+            }
+
+            info!("creating actor {}", path.logical_path());
+            let inner_cell = InnerActorCell::new(props,
+                                                 inner.system.clone(),
+                                                 self.actor_ref(),
+                                                 path.clone());
+            let actor_cell = ActorCell { inner_cell: Ref::StrongRef(Arc::new(inner_cell)) };
+            let internal_ref = ActorRef::with_cell(actor_cell, path.clone());
+            let external_ref = internal_ref.clone();
+            children.insert(path.clone(), internal_ref);
+            external_ref
+        };
+        // Messages recovered from a durable mailbox are already in the mailbox.
+        let recovered = external_ref.mailbox_len();
+        inner.monitoring.lock().unwrap().insert(path.clone(), (external_ref.clone(), Arc::new(InnerActorCell::restart_child)));
+        inner.system.registry().insert(external_ref.clone());
+        external_ref.receive_system_message(SystemMessage::Start);
+        for _ in 0..recovered {
+            inner.system.enqueue_actor(external_ref.clone());
+        }
+        Ok(external_ref)
+    }
 }
 
 /// This is the API that Actors are supposed to see of their context while handling a message.
@@ -104,15 +282,34 @@ pub trait ActorContext {
     fn actor_ref(&self) -> ActorRef;
 
     /// Spawns a child actor.
-    fn actor_of(&self, props: Arc<ActorFactory>, name: String) -> Result<ActorRef, &'static str>;
+    ///
+    /// This fails if another child already has this name (or had it and is still terminating).
+    /// Names starting with a `$` are reserved for anonymous actors.
+    fn actor_of(&self, props: Arc<ActorFactory>, name: String) -> Result<ActorRef, RobotsError>;
+
+    /// Spawns a child actor with a generated unique name, such as `$a`, `$b`...
+    ///
+    /// If the child can't be created, for example because this actor is terminating, the error is
+    /// logged and the ActorRef given sends every message to the dead letters.
+    fn actor_of_anonymous(&self, props: Arc<ActorFactory>) -> ActorRef;
 
     /// Sends a Message to the targeted ActorRef.
     fn tell<MessageTo: Message>(&self, to: ActorRef, message: MessageTo);
 
     /// Creates a Future, this Future will send the message to the targetted ActorRef (and thus be
     /// the sender of the message).
+    ///
+    /// The Future is the child of the actor with the given name.
+    #[deprecated(note = "concurrent asks can't collide with `ask_anonymous`, which names the Future")]
     fn ask<MessageTo: Message>(&self, to: ActorRef, message: MessageTo, future_name: String) -> ActorRef;
 
+    /// Same as `ask`, but the Future is an anonymous child of the actor.
+    fn ask_anonymous<MessageTo: Message>(&self, to: ActorRef, message: MessageTo) -> ActorRef;
+
+    /// Same as `ask_anonymous`, but the Future fails with `FutureFailure::Timeout` if it was not completed
+    /// before the timeout.
+    fn ask_timeout<MessageTo: Message>(&self, to: ActorRef, message: MessageTo, timeout: Duration) -> ActorRef;
+
     /// Completes a Future.
     fn complete<MessageTo: Message>(&self, to: ActorRef, complete: MessageTo);
 
@@ -130,6 +327,34 @@ pub trait ActorContext {
     fn do_computation<T: Message, F: Fn(Box<Any + Send>, ActorCell) -> T + Send + Sync + 'static>
         (&self, future: ActorRef, closure: F);
 
+    /// Creates a Future completed with the output of the closure applied to the value of the given
+    /// Future.
+    /// The given Future is then dropped.
+    fn map<T: Message, U: Message, F: Fn(T) -> U + Send + Sync + 'static>
+        (&self, future: ActorRef, closure: F) -> ActorRef;
+
+    /// Creates a Future completed with the value of the Future returned by the closure, which is
+    /// applied to the value of the given Future.
+    ///
+    /// The closure is given the context of the created Future, Futures created in the closure
+    /// (with `ask` for example) are thus its children.
+    /// The given Future is then dropped.
+    fn and_then<T: Message, F: Fn(T, ActorCell) -> ActorRef + Send + Sync + 'static>
+        (&self, future: ActorRef, closure: F) -> ActorRef;
+
+    /// Creates a Future completed with a tuple of the values of the two given Futures.
+    /// The given Futures are then dropped.
+    fn zip<A: Message, B: Message>(&self, first: ActorRef, second: ActorRef) -> ActorRef;
+
+    /// Creates a Future completed with a Vec of the values of the given Futures, in the same order.
+    /// The given Futures are then dropped.
+    fn sequence<T: Message>(&self, futures: Vec<ActorRef>) -> ActorRef;
+
+    /// Creates a Future completed with the result of the first of the given Futures to be
+    /// completed.
+    /// The given Futures are then dropped.
+    fn first_completed(&self, futures: Vec<ActorRef>) -> ActorRef;
+
     /// Requests the targeted actor to stop.
     fn stop(&self, actor_ref: ActorRef);
 
@@ -157,17 +382,50 @@ pub trait ActorContext {
     /// Logical path to the actor, such as `/user/foo/bar/baz`
     fn path(&self) -> Arc<ActorPath>;
 
-    /// Future containing an Option<ActorRef> with an ActtorRef to the Actor with the given logical
-    /// path.
+    /// Future containing an Option<ActorRef> with an ActorRef to the Actor with the given logical
+    /// path, `None` if there is none.
     ///
-    /// The future will have the path: `$actor/$name_request`
+    /// Relative paths such as `../sibling` or `./child` are resolved against the path of the
+    /// actor.
+    /// The future is the child of the actor with the given name.
+    #[deprecated(note = "concurrent requests can't collide with `identify_actor_anonymous`, which names the future")]
     fn identify_actor(&self, logical_path: String, request_name: String) -> ActorRef;
 
+    /// Same as `identify_actor`, but the future is an anonymous child of the actor.
+    fn identify_actor_anonymous(&self, logical_path: String) -> ActorRef;
+
+    /// Future containing a Vec<ActorRef> with the Actors whose logical path matches the pattern,
+    /// sorted by path.
+    ///
+    /// In a pattern `*` matches any characters in a name and `**` any number of levels, such as
+    /// in `/user/workers/*` or `/user/**/db`, anonymous actors are only matched by names starting
+    /// with a `$`. Relative patterns are resolved as in `identify_actor`.
+    /// The future is an anonymous child of the actor.
+    fn select(&self, pattern: String) -> ActorRef;
+
+    /// Sends a Message to all the Actors whose logical path matches the pattern, as given by
+    /// `select`.
+    fn broadcast<MessageTo: Message>(&self, pattern: String, message: MessageTo);
+
     /// Sends a control message to the given actor.
     fn tell_control(&self, actor: ActorRef, message: ControlMessage);
 
     /// Puts the actor in a state of failure with the given reason.
-    fn fail(&self, reason: &'static str);
+    ///
+    /// Strings are turned into `RobotsError::Failed`.
+    fn fail<R: Into<RobotsError>>(&self, reason: R);
+
+    /// Subscribes the actor to the events of the given class on the EventStream of the system, such
+    /// as `TypeId::of::<MyEvent>()`.
+    ///
+    /// The subscriptions are removed when the actor stops.
+    fn subscribe(&self, class: TypeId);
+
+    /// Unsubscribes the actor from the events of the given class.
+    fn unsubscribe(&self, class: TypeId);
+
+    /// Publishes an event on the EventStream of the system, the actor is its sender.
+    fn publish<MessageTo: Message>(&self, event: MessageTo);
 }
 
 impl ActorContext for ActorCell {
@@ -175,41 +433,38 @@ impl ActorContext for ActorCell {
         ActorRef::with_cell(self.clone(), self.path())
     }
 
-    fn actor_of(&self, props: Arc<ActorFactory>, name: String) -> Result<ActorRef, &'static str> {
+    fn actor_of(&self, props: Arc<ActorFactory>, name: String) -> Result<ActorRef, RobotsError> {
         let inner = unwrap_inner!(self.inner_cell, {
             panic!("Tried to create an actor from the context of a no longer existing actor");
         });
 
-        // We check that there is no path traversal.
-        if name.find("/") != None {
-            return Err("Used a '/' in the name of an actor, this is not allowed");
+        // We check that there is no path traversal, and the names of anonymous actors can't be
+        // taken.
+        if name.find("/") != None || name.starts_with("$") {
+            return Err(RobotsError::InvalidName(name));
         }
 
-        let path = self.path().child(name);
-        info!("creating actor {}", path.logical_path());
-        let inner_cell = InnerActorCell::new(props,
-                                             inner.system.clone(),
-                                             self.actor_ref(),
-                                             path.clone());
-        let actor_cell = ActorCell { inner_cell: Ref::StrongRef(Arc::new(inner_cell)) };
-        let internal_ref = ActorRef::with_cell(actor_cell, path.clone());
-        let external_ref = internal_ref.clone();
-        inner.children.lock().unwrap().insert(path.clone(), internal_ref);
-        inner.monitoring.lock().unwrap().insert(path.clone(), (external_ref.clone(), Arc::new(InnerActorCell::restart_child)));
-        external_ref.receive_system_message(SystemMessage::Start);
-        // This is a bit messy, but we have a chicken / egg issue otherwise when creating the name
-        // resolver actor.
-        if *(path.logical_path()) != "/system/name_resolver" {
-            self.tell(inner.system.name_resolver(), ResolveRequest::Add(external_ref.clone()));
+        self.spawn_child(&inner, props, name)
+    }
+
+    fn actor_of_anonymous(&self, props: Arc<ActorFactory>) -> ActorRef {
+        match self.try_actor_of_anonymous(props) {
+            Ok(actor_ref) => actor_ref,
+            Err(error) => {
+                error!("{} could not create an anonymous child: {}", self.path().logical_path(), error);
+                ActorRef::dead_letters()
+            },
         }
-        Ok(external_ref)
     }
 
     fn tell<MessageTo: Message>(&self, to: ActorRef, message: MessageTo) {
         // FIXME(gamazeps): Code duplication.
         let path = to.path();
         match *path {
-            ActorPath::Local(_) => to.receive(InnerMessage::Message(Box::new(message)), self.actor_ref()),
+            ActorPath::Local(_) => {
+                let delivery = to.receive(InnerMessage::Message(Box::new(message)), self.actor_ref());
+                self.check_delivery(&to, delivery);
+            },
             ActorPath::Distant(ref path) => {
                 info!("Sent a message of size {} to distant actor {}:{}", mem::size_of::<MessageTo>(),
                 path.distant_logical_path(), path.addr_port());
@@ -218,18 +473,37 @@ impl ActorContext for ActorCell {
     }
 
     fn ask<MessageTo: Message>(&self, to: ActorRef, message: MessageTo, name: String) -> ActorRef {
-        let future = self.actor_of(Props::new(Arc::new(Future::new), ()), name).unwrap();
+        let future = self.named_future(name);
+        future.tell_to(to, message);
+        future
+    }
+
+    fn ask_anonymous<MessageTo: Message>(&self, to: ActorRef, message: MessageTo) -> ActorRef {
+        let future = self.new_future();
         future.tell_to(to, message);
         future
     }
 
+    fn ask_timeout<MessageTo: Message>(&self, to: ActorRef, message: MessageTo, timeout: Duration) -> ActorRef {
+        let inner = unwrap_inner!(self.inner_cell, {
+            panic!("Tried to ask from the context of a no longer existing actor");
+        });
+        let future = self.ask_anonymous(to, message);
+        inner.system.schedule_once(timeout, future.clone(), FutureTimeout);
+        future
+    }
+
     fn complete<MessageTo: Message>(&self, future: ActorRef, complete: MessageTo) {
         // FIXME(gamazeps): Code duplication.
         // This is a copy of the code in tell, but we need to do that in order to put a Box<Any> in
         // the mailbox.
         let path = future.path();
         match *path {
-            ActorPath::Local(_) => future.receive(InnerMessage::Message(Box::new(Complete::new(Box::new(complete)))), self.actor_ref()),
+            ActorPath::Local(_) => {
+                let delivery = future.receive(InnerMessage::Message(Box::new(Complete::new(Ok(Box::new(complete))))),
+                                              self.actor_ref());
+                self.check_delivery(&future, delivery);
+            },
             ActorPath::Distant(ref path) => {
                 info!("Sent a message of size {} to distant future {}:{}", mem::size_of::<MessageTo>(),
                 path.distant_logical_path(), path.addr_port());
@@ -239,16 +513,20 @@ impl ActorContext for ActorCell {
 
     fn forward_result<T: Message>(&self, future: ActorRef, actor: ActorRef) {
         self.tell(future, Computation::Forward(actor, Arc::new(move |value, context, to| {
-            let value = Box::<Any + Send>::downcast::<T>(value).expect("Message of the wrong type");
-            context.tell(to, *value);
+            match value.and_then(|value| value.downcast::<T>().map_err(|_| FutureFailure::WrongType)) {
+                Ok(value) => context.tell(to, *value),
+                Err(failure) => context.tell(to, failure),
+            }
             FutureState::Extracted
         })));
     }
 
     fn forward_result_to_future<T: Message>(&self, future: ActorRef, actor: ActorRef) {
         self.tell(future, Computation::Forward(actor, Arc::new(move |value, context, to| {
-            let value = Box::<Any + Send>::downcast::<T>(value).expect("Message of the wrong type");
-            context.complete(to, *value);
+            match value.and_then(|value| value.downcast::<T>().map_err(|_| FutureFailure::WrongType)) {
+                Ok(value) => context.complete(to, *value),
+                Err(failure) => complete_future(&context, to, Err(failure)),
+            }
             FutureState::Extracted
         })));
     }
@@ -261,6 +539,69 @@ impl ActorContext for ActorCell {
         })));
     }
 
+    fn map<T: Message, U: Message, F: Fn(T) -> U + Send + Sync + 'static>
+        (&self, future: ActorRef, closure: F) -> ActorRef {
+        let result = self.new_future();
+        self.tell(future, forward_to_future(result.clone()));
+        self.tell(result.clone(), Computation::Computation(Arc::new(move |value, _context| {
+            match value.downcast::<T>() {
+                Ok(value) => FutureState::Computing(Box::new(closure(*value))),
+                Err(_) => FutureState::Failed(FutureFailure::WrongType),
+            }
+        })));
+        result
+    }
+
+    fn and_then<T: Message, F: Fn(T, ActorCell) -> ActorRef + Send + Sync + 'static>
+        (&self, future: ActorRef, closure: F) -> ActorRef {
+        let result = self.new_future();
+        self.tell(future, forward_to_future(result.clone()));
+        self.tell(result.clone(), Computation::FlatMap(Arc::new(move |value, context| {
+            match value.downcast::<T>() {
+                Ok(value) => Ok(closure(*value, context)),
+                Err(_) => Err(FutureFailure::WrongType),
+            }
+        })));
+        result
+    }
+
+    fn zip<A: Message, B: Message>(&self, first: ActorRef, second: ActorRef) -> ActorRef {
+        let result = self.new_future();
+        self.gather(vec![first, second], result.clone(), Arc::new(|mut values| {
+            let second = values.pop().unwrap().downcast::<B>();
+            let first = values.pop().unwrap().downcast::<A>();
+            match (first, second) {
+                (Ok(first), Ok(second)) => Ok(Box::new((*first, *second))),
+                _ => Err(FutureFailure::WrongType),
+            }
+        }));
+        result
+    }
+
+    fn sequence<T: Message>(&self, futures: Vec<ActorRef>) -> ActorRef {
+        let result = self.new_future();
+        self.gather(futures, result.clone(), Arc::new(|values| {
+            let mut sequence = Vec::with_capacity(values.len());
+            for value in values {
+                match value.downcast::<T>() {
+                    Ok(value) => sequence.push(*value),
+                    Err(_) => return Err(FutureFailure::WrongType),
+                }
+            }
+            Ok(Box::new(sequence))
+        }));
+        result
+    }
+
+    fn first_completed(&self, futures: Vec<ActorRef>) -> ActorRef {
+        let result = self.new_future();
+        // The Future keeps the first result it is given and drops the others.
+        for future in futures {
+            self.tell(future, forward_to_future(result.clone()));
+        }
+        result
+    }
+
     fn sender(&self) -> ActorRef {
         let inner = unwrap_inner!(self.inner_cell, {
             panic!("Tried to get a sender from the context of a no longer existing actor");
@@ -273,7 +614,10 @@ impl ActorContext for ActorCell {
     fn tell_control(&self, actor: ActorRef, message: ControlMessage) {
         let path = actor.path();
         match *path {
-            ActorPath::Local(_) => actor.receive(InnerMessage::Control(message), self.actor_ref()),
+            ActorPath::Local(_) => {
+                let delivery = actor.receive(InnerMessage::Control(message), self.actor_ref());
+                self.check_delivery(&actor, delivery);
+            },
             ActorPath::Distant(_) => {},
         }
     }
@@ -323,6 +667,9 @@ impl ActorContext for ActorCell {
             panic!("tried to have a no longer existing actor monitor an other actor?")
         });
         self.tell_control(actor.clone(), ControlMessage::RegisterMonitoring);
+        if let ActorPath::Distant(_) = *actor.path() {
+            inner.system.monitor_distant(self.actor_ref(), actor.clone());
+        }
         let mut monitoring = inner.monitoring.lock().unwrap();
         monitoring.insert(actor.path(), (actor, handler));
     }
@@ -335,22 +682,70 @@ impl ActorContext for ActorCell {
     }
 
     fn identify_actor(&self, name: String, request_name: String) -> ActorRef {
+        let future = self.named_future(request_name);
+        self.identify(name, future)
+    }
+
+    fn identify_actor_anonymous(&self, name: String) -> ActorRef {
+        let future = self.new_future();
+        self.identify(name, future)
+    }
+
+    fn select(&self, pattern: String) -> ActorRef {
         let inner = unwrap_inner!(self.inner_cell, {
-            panic!("Tried to get the actor system of a no longer existing actor while resolving \
-                    a path. This should *never* happen");
+            panic!("Tried to get the actor system of a no longer existing actor while selecting \
+                    actors. This should *never* happen");
+        });
+        let future = self.new_future();
+        self.complete(future.clone(), inner.system.registry().select(&inner.path.resolve(&pattern)));
+        future
+    }
+
+    fn broadcast<MessageTo: Message>(&self, pattern: String, message: MessageTo) {
+        let inner = unwrap_inner!(self.inner_cell, {
+            panic!("Tried to get the actor system of a no longer existing actor while selecting \
+                    actors. This should *never* happen");
         });
-        self.ask(inner.system.name_resolver(), ResolveRequest::Get(name), request_name)
+        for actor in inner.system.registry().select(&inner.path.resolve(&pattern)) {
+            self.tell(actor, message.clone());
+        }
     }
 
-    fn fail(&self, reason: &'static str) {
+    fn fail<R: Into<RobotsError>>(&self, reason: R) {
+        let reason = reason.into();
         let inner = unwrap_inner!(self.inner_cell, {
             panic!("Tried to get the state of a no longer existing actor while resolving \
                     a path. This should *never* happen");
         });
         {*inner.actor_state.write().unwrap() = ActorState::Failed;}
         for actor in self.monitored_by().iter() {
-            self.tell_control(actor.clone(), ControlMessage::Failure(Failure::new(self.actor_ref(), reason)));
+            self.tell_control(actor.clone(), ControlMessage::Failure(Failure::new(self.actor_ref(), reason.clone())));
         }
+        inner.system.event_stream().publish(ActorFailed {
+            actor: self.actor_ref(),
+            reason: reason,
+        });
+    }
+
+    fn subscribe(&self, class: TypeId) {
+        let inner = unwrap_inner!(self.inner_cell, {
+            panic!("Tried to subscribe a no longer existing actor to events");
+        });
+        inner.system.event_stream().subscribe(self.actor_ref(), class);
+    }
+
+    fn unsubscribe(&self, class: TypeId) {
+        let inner = unwrap_inner!(self.inner_cell, {
+            panic!("Tried to unsubscribe a no longer existing actor from events");
+        });
+        inner.system.event_stream().unsubscribe(&self.actor_ref(), class);
+    }
+
+    fn publish<MessageTo: Message>(&self, event: MessageTo) {
+        let inner = unwrap_inner!(self.inner_cell, {
+            panic!("Tried to publish an event from the context of a no longer existing actor");
+        });
+        inner.system.event_stream().publish_from(event, self.actor_ref());
     }
 }
 
@@ -388,7 +783,28 @@ impl Failsafe {
 impl Drop for Failsafe {
     fn drop(&mut self) {
         if self.active {
-            self.context.fail("panic");
+            self.context.fail(RobotsError::Panicked);
+        }
+    }
+}
+
+/// Acknowledges a message of a durable mailbox once the actor handled it, when this is dropped.
+///
+/// A message whose handling panicked is not acknowledged, it is thus given again to the next actor
+/// created with the same path.
+struct Acknowledgement {
+    storage: Arc<MailboxStorage>,
+    path: Arc<ActorPath>,
+    id: u64,
+}
+
+impl Drop for Acknowledgement {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            return;
+        }
+        if let Err(error) = self.storage.acknowledge(self.path.logical_path(), self.id) {
+            warn!("A message of {} could not be acknowledged: {}", self.path.logical_path(), error);
         }
     }
 }
@@ -410,6 +826,8 @@ pub enum SystemMessage {
 struct Envelope {
     message: InnerMessage,
     sender: ActorRef,
+    // Id of the message in the durable mailbox, if it was stored there.
+    durable_id: Option<u64>,
 }
 
 /// Types of message that can be sent to an actor that will be treated normally.
@@ -442,11 +860,11 @@ pub enum ControlMessage {
 /// Structurer containing Actor Failure informations.
 pub struct Failure {
     source: ActorRef,
-    reason: &'static str,
+    reason: RobotsError,
 }
 
 impl Failure {
-    fn new(source: ActorRef, reason: &'static str) -> Failure {
+    pub(crate) fn new(source: ActorRef, reason: RobotsError) -> Failure {
         Failure {
             source: source,
             reason: reason,
@@ -455,23 +873,30 @@ impl Failure {
     /// Actor that failed.
     pub fn actor(&self) -> ActorRef {self.source.clone()}
     /// Reason of failure.
-    pub fn reason(&self) -> &'static str {self.reason}
+    pub fn reason(&self) -> &RobotsError {&self.reason}
 }
 
 struct InnerActorCell {
     mailbox: Mutex<VecDeque<Envelope>>,
+    mailbox_storage: Option<Arc<MailboxStorage>>,
     system_mailbox: Mutex<VecDeque<SystemMessage>>,
     props: Arc<ActorFactory>,
     system: ActorSystem,
     path: Arc<ActorPath>,
+    // Unique id of this incarnation of the actor in the system.
+    uid: u64,
     current_sender: Mutex<Option<ActorRef>>,
     busy: Mutex<()>,
     father: ActorRef,
     children: Mutex<HashMap<Arc<ActorPath>, ActorRef>>,
+    // Killed children that may not be dropped yet, their names can't be reused until they are.
+    terminating_children: Mutex<HashMap<Arc<ActorPath>, ActorRef>>,
     monitoring: Mutex<HashMap<Arc<ActorPath>, (ActorRef, FailureHandler)>>,
     actor_state: Arc<RwLock<ActorState>>,
     monitored_by: Mutex<Vec<ActorRef>>,
     actor: RwLock<Arc<Actor>>,
+    // Number of anonymous children created so far, used to generate their names.
+    anonymous_children: Mutex<u64>,
 }
 
 impl InnerActorCell {
@@ -481,32 +906,87 @@ impl InnerActorCell {
            father: ActorRef,
            path: Arc<ActorPath>)
            -> InnerActorCell {
+        let mailbox_storage = props.mailbox_storage();
         InnerActorCell {
             actor: RwLock::new(props.create()),
-            mailbox: Mutex::new(VecDeque::new()),
+            mailbox: Mutex::new(InnerActorCell::recover_mailbox(&mailbox_storage, &path)),
+            mailbox_storage: mailbox_storage,
             system_mailbox: Mutex::new(VecDeque::new()),
             props: props,
+            uid: system.next_uid(),
             system: system,
             path: path,
             current_sender: Mutex::new(None),
             busy: Mutex::new(()),
             father: father.clone(),
             children: Mutex::new(HashMap::new()),
+            terminating_children: Mutex::new(HashMap::new()),
             monitoring: Mutex::new(HashMap::new()),
             actor_state: Arc::new(RwLock::new(ActorState::Unstarted)),
             monitored_by: Mutex::new(vec![father.clone()]),
+            anonymous_children: Mutex::new(0),
         }
     }
 
+    /// Generates a unique name for an anonymous child: `$a`, `$b`, ..., `$z`, `$ba`, `$bb`...
+    fn anonymous_name(&self) -> String {
+        let mut anonymous_children = self.anonymous_children.lock().unwrap();
+        let mut n = *anonymous_children;
+        *anonymous_children += 1;
+        let mut letters = Vec::new();
+        loop {
+            letters.push((b'a' + (n % 26) as u8) as char);
+            n /= 26;
+            if n == 0 {
+                break;
+            }
+        }
+        letters.push('$');
+        letters.into_iter().rev().collect()
+    }
+
+    fn mailbox_len(&self) -> usize {
+        self.mailbox.lock().unwrap().len()
+    }
+
     fn receive_envelope(&self, envelope: Envelope) {
         self.mailbox.lock().unwrap().push_back(envelope);
     }
 
-    fn receive_message(&self, message: InnerMessage, sender: ActorRef) {
+    /// Puts the message in the mailbox, once it is stored if the actor has a durable mailbox.
+    fn receive_message(&self, message: InnerMessage, sender: ActorRef) -> Result<(), RobotsError> {
+        let durable_id = match (&self.mailbox_storage, &message) {
+            (&Some(ref storage), &InnerMessage::Message(ref message)) => {
+                storage.append(self.path.logical_path(), &**message)?
+            },
+            _ => None,
+        };
         self.receive_envelope(Envelope {
             message: message,
             sender: sender,
+            durable_id: durable_id,
         });
+        Ok(())
+    }
+
+    /// Gives the messages of the durable mailbox of the actor that were not handled by its
+    /// previous incarnation, if it has a durable mailbox.
+    fn recover_mailbox(storage: &Option<Arc<MailboxStorage>>, path: &ActorPath) -> VecDeque<Envelope> {
+        let storage = match *storage {
+            Some(ref storage) => storage,
+            None => return VecDeque::new(),
+        };
+        match storage.recover(path.logical_path()) {
+            Ok(messages) => messages.into_iter().map(|(id, message)| Envelope {
+                message: InnerMessage::Message(message),
+                sender: ActorRef::dead_letters(),
+                durable_id: Some(id),
+            }).collect(),
+            Err(error) => {
+                warn!("The messages of {} could not be recovered: {}", path.logical_path(), error);
+                VecDeque::new()
+            },
+        }
     }
 
     fn receive_system_message(&self, system_message: SystemMessage) {
@@ -548,18 +1028,39 @@ impl InnerActorCell {
                 let actor = self.actor.read().unwrap();
                 match envelope.message {
                     InnerMessage::Message(message) => {
+                        let _acknowledgement = match (&self.mailbox_storage, envelope.durable_id) {
+                            (&Some(ref storage), Some(id)) => Some(Acknowledgement {
+                                storage: storage.clone(),
+                                path: self.path.clone(),
+                                id: id,
+                            }),
+                            _ => None,
+                        };
                         actor.receive(message, context);
                     },
                     InnerMessage::Control(message) => {
                         match message {
                             ControlMessage::PoisonPill => context.kill_me(),
                             ControlMessage::Failure(failure) => {
-                                let monitoring = self.monitoring.lock().unwrap();
-                                let handler = monitoring.get(&failure.actor().path())
-                                    .expect("Received a failure notification from an unknown actor");
-                                (*handler.1)(failure, context);
+                                let handler = {
+                                    let monitoring = self.monitoring.lock().unwrap();
+                                    match monitoring.get(&failure.actor().path()) {
+                                        // The failure may come from a previous incarnation of
+                                        // the monitored actor, that one is already gone.
+                                        Some(&(ref actor, ref handler)) if actor.uid() == failure.actor().uid() => {
+                                            Some(handler.clone())
+                                        },
+                                        _ => None,
+                                    }
+                                };
+                                match handler {
+                                    Some(handler) => (*handler)(failure, context),
+                                    None => warn!("{} received a failure notification from an unknown actor {}",
+                                                  self.path.logical_path(),
+                                                  failure.actor().path().logical_path()),
+                                }
                             },
-                            ControlMessage::KillMe(actor_ref) => self.kill(actor_ref, context),
+                            ControlMessage::KillMe(actor_ref) => self.kill(actor_ref),
                             ControlMessage::RegisterMonitoring => {
                                 let mut mon = self.monitored_by.lock().unwrap();
                                 mon.push(context.sender());
@@ -575,16 +1076,34 @@ impl InnerActorCell {
         failsafe.cancel();
     }
 
-    fn kill(&self, actor: ActorRef, context: ActorCell) {
-        self.children.lock().unwrap().remove(&actor.path()).expect(&format!("actor {} was asked to kill {} and cannot do that",
-                                             self.path.logical_path(),
-                                             actor.path().logical_path()));
-        context.tell(self.system.name_resolver(), ResolveRequest::Remove(actor.path()));
+    fn kill(&self, actor: ActorRef) {
+        let child = {
+            let mut children = self.children.lock().unwrap();
+            // A previous incarnation of the child may ask to be killed again, that must not kill
+            // the current one.
+            if children.get(&actor.path()).map_or(true, |child| child.uid() != actor.uid()) {
+                warn!("actor {} was asked to kill {} and cannot do that",
+                      self.path.logical_path(),
+                      actor.path().logical_path());
+                return;
+            }
+            children.remove(&actor.path()).unwrap()
+        };
+        {
+            // The child can still be handling a message on another thread, so we keep a weak
+            // reference to it to know when it is really dropped.
+            let mut terminating_children = self.terminating_children.lock().unwrap();
+            terminating_children.retain(|_, child| child.is_alive());
+            terminating_children.insert(actor.path(), child.clone());
+        }
+        drop(child);
+        self.system.registry().remove(actor.path().logical_path());
     }
 
     fn start(&self, context: ActorCell) {
-        self.actor.write().unwrap().pre_start(context);
+        self.actor.write().unwrap().pre_start(context.clone());
         *self.actor_state.write().unwrap() = ActorState::Running;
+        self.system.event_stream().publish(ActorStarted { actor: context.actor_ref() });
     }
 
     fn restart(&self, context: ActorCell) {
@@ -607,5 +1126,19 @@ impl Drop for InnerActorCell {
         let actor = self.actor.write().unwrap();
         info!("Actor {} is dropped", *self.path.logical_path());
         actor.post_stop();
+        // Actors dropped along with their father were not killed, and are thus unregistered here.
+        self.system.registry().remove_dead(self.path.logical_path());
+        // The actor may be dropped while the event stream is locked, so its subscriptions are
+        // removed and its stop published from the scheduler thread.
+        let system = self.system.clone();
+        let path = self.path.clone();
+        let uid = self.uid;
+        self.system.schedule(Duration::from_secs(0), Box::new(move || {
+            system.event_stream().remove_subscriber(uid);
+            system.event_stream().publish(ActorStopped {
+                path: path.clone(),
+                uid: uid,
+            });
+        }));
     }
 }
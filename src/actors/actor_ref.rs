@@ -41,6 +41,28 @@ impl ActorPath {
         }
     }
 
+    /// Resolves a path relative to this one, and gives the logical path it points to.
+    ///
+    /// Absolute paths (starting with a `/`) are only normalized, `.` is the actor itself and `..`
+    /// its father, so that `../sibling` or `./child` can be used.
+    pub fn resolve(&self, path: &str) -> String {
+        let mut segments: Vec<&str> = if path.starts_with('/') {
+            Vec::new()
+        } else {
+            self.logical_path().split('/').filter(|segment| !segment.is_empty()).collect()
+        };
+        for segment in path.split('/') {
+            match segment {
+                "" | "." => {},
+                ".." => {
+                    let _ = segments.pop();
+                },
+                segment => segments.push(segment),
+            }
+        }
+        format!("/{}", segments.join("/"))
+    }
+
     /// Creates an ActorPath for a child of an actor.
     ///
     /// This gives a Local variant, because actors are always created locally.
@@ -79,6 +101,17 @@ impl ConnectionInfo {
     }
 }
 
+/// Outcome of the delivery of a message to an ActorRef.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Delivery {
+    /// The message was put in the mailbox of a local actor.
+    Delivered,
+    /// The actor is distant, the message was given to the remoting layer.
+    Remote,
+    /// The actor is stopped (or never existed), the message was dropped.
+    DeadLetter,
+}
+
 #[derive(Clone)]
 enum InnerActor {
     Cthulhu(Cthulhu),
@@ -95,6 +128,7 @@ enum InnerActor {
 pub struct ActorRef {
     inner_actor: Option<InnerActor>,
     path: Arc<ActorPath>,
+    uid: u64,
 }
 
 impl ActorRef {
@@ -103,6 +137,7 @@ impl ActorRef {
         ActorRef {
             inner_actor: None,
             path: path,
+            uid: 0,
         }
     }
 
@@ -112,46 +147,108 @@ impl ActorRef {
         ActorRef {
             inner_actor: Some(InnerActor::Cthulhu(cthulhu)),
             path: path,
+            uid: 0,
+        }
+    }
+
+    /// Creates an ActorRef to which every message is a dead letter, used as the sender of messages
+    /// that do not come from an actor.
+    pub(crate) fn dead_letters() -> ActorRef {
+        ActorRef {
+            inner_actor: None,
+            path: ActorPath::new_local("/deadLetters".to_owned()),
+            uid: 0,
         }
     }
 
     /// Creates a new ActorRef for a local Actor, with the given ActorCell.
     pub fn with_cell(cell: ActorCell, path: Arc<ActorPath>) -> ActorRef {
+        // A stopped actor has no uid, no uid is ever 0 so messages will be dead letters.
+        let uid = cell.uid().unwrap_or(0);
         ActorRef {
             inner_actor: Some(InnerActor::Actor(cell)),
             path: path,
+            uid: uid,
         }
     }
 
     /// Receives a system message such as `Start`, `Restart` or a `Failure(ActorRef)`, puts it in
     /// the system mailbox and schedules the actor if needed.
-    pub fn receive_system_message(&self, system_message: SystemMessage) {
+    pub fn receive_system_message(&self, system_message: SystemMessage) -> Delivery {
         info!("{} receiving a system message", self.path().logical_path());
-        let inner = self.inner_actor.as_ref().expect("Tried to put a system message in the mailbox of a distant actor.");
-        match *inner {
-            InnerActor::Actor(ref actor) => actor.receive_system_message(system_message),
-            InnerActor::Cthulhu(ref cthulhu) => cthulhu.receive_system_message(),
-        };
+        match self.inner_actor {
+            Some(InnerActor::Actor(ref actor)) => actor.receive_system_message(system_message, self.uid),
+            Some(InnerActor::Cthulhu(ref cthulhu)) => cthulhu.receive_system_message(),
+            None => self.undeliverable(),
+        }
     }
 
     /// Receives a regular message and puts it in the mailbox and schedules the actor if needed.
-    pub fn receive(&self, message: InnerMessage, sender: ActorRef) {
+    pub fn receive(&self, message: InnerMessage, sender: ActorRef) -> Delivery {
         info!("{} receiving a message", self.path().logical_path());
-        let inner = self.inner_actor.as_ref().expect("Tried to put a message in the mailbox of a distant actor.");
-        match *inner {
-            InnerActor::Actor(ref actor) => actor.receive_message(message, sender),
-            InnerActor::Cthulhu(ref cthulhu) => cthulhu.receive(),
-        };
+        match self.inner_actor {
+            Some(InnerActor::Actor(ref actor)) => actor.receive_message(message, sender, self.uid),
+            Some(InnerActor::Cthulhu(ref cthulhu)) => cthulhu.receive(),
+            None => self.undeliverable(),
+        }
     }
 
     /// Handles a messages by calling the `receive` method of the underlying actor.
     pub fn handle(&self) {
         info!("{} handling a message", self.path().logical_path());
-        let inner = self.inner_actor.as_ref().expect("");
-        match *inner {
-            InnerActor::Actor(ref actor) => actor.handle_envelope(),
-            InnerActor::Cthulhu(ref cthulhu) => cthulhu.handle(),
-        };
+        match self.inner_actor {
+            Some(InnerActor::Actor(ref actor)) => actor.handle_envelope(),
+            Some(InnerActor::Cthulhu(ref cthulhu)) => cthulhu.handle(),
+            None => warn!("{} was asked to handle a message but is not local", self.path().logical_path()),
+        }
+    }
+
+    /// Outcome of a delivery to an ActorRef without a local actor.
+    fn undeliverable(&self) -> Delivery {
+        match *self.path {
+            ActorPath::Distant(ref path) => {
+                info!("Routed a message to distant actor {}:{}", path.distant_logical_path(), path.addr_port());
+                Delivery::Remote
+            },
+            ActorPath::Local(ref path) => {
+                warn!("A message was sent to {} which has no local actor", path);
+                Delivery::DeadLetter
+            },
+        }
+    }
+
+    /// Gives the child of a local actor with the given name, if any.
+    pub(crate) fn child(&self, name: &str) -> Option<ActorRef> {
+        match self.inner_actor {
+            Some(InnerActor::Actor(ref actor)) => actor.child(name),
+            _ => None,
+        }
+    }
+
+    /// Number of messages waiting in the mailbox of the actor, 0 if it is not a local actor.
+    pub fn mailbox_len(&self) -> usize {
+        match self.inner_actor {
+            Some(InnerActor::Actor(ref actor)) => actor.mailbox_len(),
+            _ => 0,
+        }
+    }
+
+    /// Tells whether the actor is still alive, that is whether it can still receive messages.
+    ///
+    /// Distant actors are always considered alive, as we can't know better.
+    pub fn is_alive(&self) -> bool {
+        match self.inner_actor {
+            Some(InnerActor::Actor(ref actor)) => actor.is_alive(),
+            _ => true,
+        }
+    }
+
+    /// Unique id of the incarnation of the actor this ActorRef points to.
+    ///
+    /// Two actors created with the same path (one after the other) have different uids, messages
+    /// sent to a previous incarnation are dead letters. This is 0 for distant actors and Cthulhu.
+    pub fn uid(&self) -> u64 {
+        self.uid
     }
 
     /// Gives a clone of the ActorPath.
@@ -160,7 +257,7 @@ impl ActorRef {
     }
 
     /// Makes this ActorRef send a message to anther ActorRef.
-    pub fn tell_to<MessageTo: Message>(&self, to: ActorRef, message: MessageTo) {
+    pub fn tell_to<MessageTo: Message>(&self, to: ActorRef, message: MessageTo) -> Delivery {
         info!("{} is sending a message to {}", self.path().logical_path(), to.path().logical_path());
         let message: Box<Any + Send> = Box::new(message);
         to.receive(InnerMessage::Message(message), self.clone())
@@ -172,6 +269,7 @@ impl Clone for ActorRef {
         ActorRef {
             inner_actor: self.inner_actor.clone(),
             path: self.path.clone(),
+            uid: self.uid,
         }
     }
 }
@@ -1,14 +1,18 @@
 use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
 use std::thread;
+use std::time::Duration;
 
-use actors::{ActorPath, ActorRef, Message, Props};
-use actors::actor_cell::{ActorCell, SystemMessage};
+use actors::{ActorPath, ActorRef, DeadLetter, Delivery, EventStream, InnerMessage, Message, Props, RobotsError};
+use actors::actor_cell::{ActorCell, ControlMessage, Failure, SystemMessage};
 use actors::cthulhu::Cthulhu;
-use actors::future::{Future, FutureExtractor};
+use actors::future::{AsyncResult, Complete, Future, FutureExtractor, FutureFailure, FutureTimeout};
 use actors::name_resolver::NameResolver;
 use actors::props::ActorFactory;
+use actors::registry::Registry;
 use actors::root_actor::RootActor;
+use actors::scheduler::Scheduler;
 
 /// This is failsafe used to relaunch consumer threads if they panic!.
 struct Relauncher {
@@ -82,20 +86,44 @@ impl ActorSystem {
         *actor_system.inner.system_actor.write().unwrap() = Some(system_actor);
         actor_system.spawn_threads(1);
         info!("Launched the first thread");
-        let name_resolver = actor_system.system_actor_of(Props::new(Arc::new(NameResolver::new), ()), "name_resolver".to_owned());
+        let name_resolver = actor_system.system_actor_of(Props::new(Arc::new(NameResolver::new), actor_system.clone()),
+                                                         "name_resolver".to_owned())
+                                        .unwrap();
         info!("Created the /system/name_resolver actor");
         *actor_system.inner.name_resolver.write().unwrap() = Some(name_resolver);
         actor_system
     }
 
     /// Spawns an Actor created using the Props given for the user.
-    pub fn actor_of(&self, props: Arc<ActorFactory>, name: String) -> ActorRef {
-        self.inner.actor_of(props, name)
+    ///
+    /// This fails if the name is already taken by another actor of the user, or if the system was
+    /// shut down.
+    /// Names starting with a `$` are reserved for anonymous actors.
+    pub fn actor_of(&self, props: Arc<ActorFactory>, name: String) -> Result<ActorRef, RobotsError> {
+        self.inner.actor_of(props, Some(name))
+    }
+
+    /// Spawns an Actor created using the Props given for the user, with a generated unique name
+    /// such as `/user/$a`.
+    ///
+    /// If the actor can't be created, for example because the system was shut down, the error is
+    /// logged and the ActorRef given sends every message to the dead letters.
+    pub fn actor_of_anonymous(&self, props: Arc<ActorFactory>) -> ActorRef {
+        match self.inner.actor_of(props, None) {
+            Ok(actor_ref) => actor_ref,
+            Err(error) => {
+                error!("An anonymous actor could not be created: {}", error);
+                ActorRef::dead_letters()
+            },
+        }
     }
 
     /// Spawns an Actor created using the Props given for the system.
-    pub fn system_actor_of(&self, props: Arc<ActorFactory>, name: String) -> ActorRef {
-        self.inner.system_actor_of(props, name)
+    ///
+    /// This fails if the name is already taken by another actor of the system, or if the system
+    /// was shut down.
+    pub fn system_actor_of(&self, props: Arc<ActorFactory>, name: String) -> Result<ActorRef, RobotsError> {
+        self.inner.system_actor_of(props, Some(name))
     }
 
     /// Shuts the actor system down.
@@ -169,6 +197,9 @@ impl ActorSystem {
     }
 
     /// Gives the ActorRef of the name resolver actor.
+    ///
+    /// It answers `ResolveRequest`s with the registry of the actors, so it has no state to lose
+    /// when it is restarted.
     pub fn name_resolver(&self) -> ActorRef {
         match self.inner.name_resolver.read().unwrap().as_ref() {
             None => panic!("The name resolver is not initialized."),
@@ -176,39 +207,223 @@ impl ActorSystem {
         }
     }
 
+    /// Gives the living actor with the given logical path, if any.
+    ///
+    /// This reads the registry of the actors, and walks down the actor tree from the root actors
+    /// if the actor is not registered (as the root actors themselves). No message is sent.
+    pub fn lookup(&self, logical_path: &str) -> Option<ActorRef> {
+        self.inner.registry.get(logical_path).or_else(|| self.walk_tree(logical_path))
+    }
+
+    /// Finds an actor by walking down the children of the root actors.
+    fn walk_tree(&self, logical_path: &str) -> Option<ActorRef> {
+        let mut names = logical_path.split('/').filter(|name| !name.is_empty());
+        let root = match names.next() {
+            Some("user") => self.inner.user_actor.read().unwrap().clone(),
+            Some("system") => self.inner.system_actor.read().unwrap().clone(),
+            _ => None,
+        };
+        names.fold(root, |actor, name| actor.and_then(|actor| actor.child(name)))
+    }
+
+    /// Gives a new uid for an actor incarnation, uids start at 1.
+    pub(crate) fn next_uid(&self) -> u64 {
+        self.inner.last_uid.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Gives the registry of the actors by logical path.
+    pub(crate) fn registry(&self) -> &Registry {
+        &self.inner.registry
+    }
+
+    /// Records that the local actor monitors the distant one, so that it is notified when the node
+    /// of the distant actor fails.
+    pub(crate) fn monitor_distant(&self, monitoring: ActorRef, distant: ActorRef) {
+        self.inner.distant_monitoring.lock().unwrap().push((distant, monitoring));
+    }
+
+    /// Notifies the actors monitoring actors of the node with the given address that it can't be
+    /// reached, they fail with `RobotsError::Unreachable`.
+    pub(crate) fn distant_node_unreachable(&self, addr_port: &str) {
+        self.notify_distant_monitoring(addr_port, false);
+    }
+
+    /// Notifies the actors monitoring actors of the node with the given address that it was
+    /// removed, they fail with `RobotsError::ActorStopped` and are no longer monitored.
+    pub(crate) fn distant_node_removed(&self, addr_port: &str) {
+        self.notify_distant_monitoring(addr_port, true);
+    }
+
+    fn notify_distant_monitoring(&self, addr_port: &str, removed: bool) {
+        let mut distant_monitoring = self.inner.distant_monitoring.lock().unwrap();
+        distant_monitoring.retain(|&(_, ref monitoring)| monitoring.is_alive());
+        let on_node = |distant: &ActorRef| match *distant.path() {
+            ActorPath::Distant(ref path) => path.addr_port() == addr_port,
+            ActorPath::Local(_) => false,
+        };
+        for &(ref distant, ref monitoring) in distant_monitoring.iter().filter(|&&(ref distant, _)| on_node(distant)) {
+            let reason = if removed {
+                RobotsError::ActorStopped(distant.path().logical_path().clone())
+            } else {
+                RobotsError::Unreachable(addr_port.to_owned())
+            };
+            let failure = Failure::new(distant.clone(), reason);
+            let _ = monitoring.receive(InnerMessage::Control(ControlMessage::Failure(failure)), distant.clone());
+        }
+        if removed {
+            distant_monitoring.retain(|&(ref distant, _)| !on_node(distant));
+        }
+    }
+
+    /// Gives the EventStream of the system.
+    pub fn event_stream(&self) -> &EventStream {
+        &self.inner.event_stream
+    }
+
     /// Sends a message to the given actor.
     ///
     /// The sender of the message is the user_actor, thus this expects that no answer will be
     /// given.
-    pub fn tell<M: Message>(&self, to: ActorRef, message: M) {
-        match self.inner.user_actor.read().unwrap().as_ref() {
-            Some(user_actor) => user_actor.tell_to(to, message),
-            None => unreachable!(),
+    /// Messages sent once the system is shut down are dead letters, dead letters are published on
+    /// the EventStream.
+    pub fn tell<M: Message>(&self, to: ActorRef, message: M) -> Delivery {
+        let user_actor = self.inner.user_actor.read().unwrap().clone();
+        let (delivery, sender) = match user_actor {
+            Some(user_actor) => (user_actor.tell_to(to.clone(), message), user_actor),
+            None => {
+                warn!("A message was sent to {} after the shutdown of the system", to.path().logical_path());
+                (Delivery::DeadLetter, ActorRef::dead_letters())
+            },
+        };
+        if delivery == Delivery::DeadLetter {
+            self.inner.event_stream.publish(DeadLetter { recipient: to, sender: sender });
         }
+        delivery
     }
 
     /// Creates a Future that will send the message to the targetted actor.
     ///
-    /// The father of this Future is the user_actor.
+    /// The father of this Future is the user_actor, and the Future has the given name. If it can't
+    /// be created, for example because the name is taken, the error is logged and the ActorRef
+    /// given sends every message to the dead letters.
+    #[deprecated(note = "concurrent asks can't collide with `ask_anonymous`, which names the Future")]
     pub fn ask<M: Message>(&self, to: ActorRef, message: M, name: String) -> ActorRef {
-        let future = self.actor_of(Props::new(Arc::new(Future::new), ()), name);
+        let future = match self.actor_of(Props::new(Arc::new(Future::new), ()), name) {
+            Ok(future) => future,
+            Err(error) => {
+                error!("A Future could not be created: {}", error);
+                ActorRef::dead_letters()
+            },
+        };
         future.tell_to(to, message);
         future
     }
 
+    /// Same as `ask`, but the Future gets a generated unique name such as `/user/$a`.
+    pub fn ask_anonymous<M: Message>(&self, to: ActorRef, message: M) -> ActorRef {
+        let future = self.actor_of_anonymous(Props::new(Arc::new(Future::new), ()));
+        future.tell_to(to, message);
+        future
+    }
+
+    /// Same as `ask_anonymous`, but the Future fails with `FutureFailure::Timeout` if it was not
+    /// completed before the timeout.
+    pub fn ask_timeout<M: Message>(&self, to: ActorRef, message: M, timeout: Duration) -> ActorRef {
+        let future = self.ask_anonymous(to, message);
+        self.schedule_once(timeout, future.clone(), FutureTimeout);
+        future
+    }
+
+    /// Creates a Future containing a Vec<ActorRef> with the actors whose logical path matches the
+    /// pattern, see `ActorContext::select` for the syntax of patterns.
+    ///
+    /// Relative patterns are resolved against the root `/`.
+    pub fn select(&self, pattern: String) -> ActorRef {
+        let pattern = ActorPath::Local("/".to_owned()).resolve(&pattern);
+        let future = self.actor_of_anonymous(Props::new(Arc::new(Future::new), ()));
+        let selection = Complete::new(Ok(Box::new(self.inner.registry.select(&pattern))));
+        let _ = future.receive(InnerMessage::Message(Box::new(selection)), future.clone());
+        future
+    }
+
+    /// Sends a message to all the actors whose logical path matches the pattern.
+    ///
+    /// As with `tell` the sender of the messages is the user_actor.
+    pub fn broadcast<M: Message>(&self, pattern: String, message: M) {
+        let pattern = ActorPath::Local("/".to_owned()).resolve(&pattern);
+        for actor in self.inner.registry.select(&pattern) {
+            self.tell(actor, message.clone());
+        }
+    }
+
     /// Extracts the result from a Future.
     ///
     /// This is not supposed to be used a lot as this is a synchronous call (if an actor wants to
     /// get the result of a fututure it should use forward_result instead).
     ///
     /// The extraction creates an Extractor actor whose father is the user_actor.
+    ///
+    /// This panics if the Future failed, use `extract_result_timeout` to get the failure instead.
     pub fn extract_result<M: Message>(&self, future: ActorRef) -> M {
+        match self.extract(future) {
+            Ok(result) => result,
+            Err(failure) => panic!("Tried to extract the result of a failed Future: {}", failure),
+        }
+    }
+
+    /// Extracts the result from a Future, waiting at most for the given timeout.
+    ///
+    /// If the Future was not completed in time this gives `RobotsError::Timeout`, the other
+    /// failures of the Future are given as `RobotsError::FutureFailed`.
+    pub fn extract_result_timeout<M: Message>(&self, future: ActorRef, timeout: Duration) -> Result<M, RobotsError> {
+        self.schedule_once(timeout, future.clone(), FutureTimeout);
+        self.extract(future).map_err(RobotsError::from)
+    }
+
+    fn extract<M: Message>(&self, future: ActorRef) -> Result<M, FutureFailure> {
         // NOTE: this creates a lot of things but this is not meant to be used outside of
         // tests or examples so this is fine by my book.
         let (tx, rx) = channel();
-        let _extractor = self.actor_of(Props::new(Arc::new(FutureExtractor::new), (future, Arc::new(Mutex::new(tx)))), "extractor".to_owned());
+        let tx = Mutex::new(tx);
+        let callback = Arc::new(move |result| {
+            // FIXME(gamazeps): error handling.
+            let _res = tx.lock().unwrap().send(result);
+        });
+        let _extractor = self.actor_of_anonymous(Props::new(Arc::new(FutureExtractor::new), (future, callback)));
         rx.recv().unwrap()
     }
+
+    /// Gives the result of a Future as a `std::future::Future`, so that it can be awaited by
+    /// asynchronous code instead of blocking a thread as `extract_result` does.
+    ///
+    /// As for `extract_result`, this creates an Extractor actor whose father is the user_actor.
+    pub fn async_result<M: Message>(&self, future: ActorRef) -> AsyncResult<M> {
+        let (result, callback) = AsyncResult::new();
+        let _extractor = self.actor_of_anonymous(Props::new(Arc::new(FutureExtractor::new), (future, callback)));
+        result
+    }
+
+    /// Sends the message to the given actor once the delay has passed.
+    ///
+    /// As with `tell`, the sender of the message is the user_actor. Once the system is shut down
+    /// the message is simply dropped.
+    pub fn schedule_once<M: Message>(&self, delay: Duration, to: ActorRef, message: M) {
+        let sender = match self.inner.user_actor.read().unwrap().as_ref() {
+            Some(user_actor) => user_actor.clone(),
+            None => {
+                return warn!("A message was scheduled for {} after the shutdown of the system",
+                             to.path().logical_path());
+            },
+        };
+        self.inner.scheduler.schedule_once(delay, Box::new(move || {
+            sender.tell_to(to.clone(), message.clone());
+        }));
+    }
+
+    /// Runs the task on the scheduler thread once the delay has passed.
+    pub(crate) fn schedule(&self, delay: Duration, task: Box<Fn() + Send>) {
+        self.inner.scheduler.schedule_once(delay, task);
+    }
 }
 
 impl Clone for ActorSystem {
@@ -232,6 +447,14 @@ struct InnerActorSystem {
     system_actor: RwLock<Option<ActorRef>>,
     // ActorRef to the name resolver.
     name_resolver: RwLock<Option<ActorRef>>,
+    // Sends messages after a delay.
+    scheduler: Scheduler,
+    registry: Registry,
+    event_stream: EventStream,
+    // Last uid given to an actor.
+    last_uid: AtomicU64,
+    // Distant actors monitored by local actors, with the monitoring actor.
+    distant_monitoring: Mutex<Vec<(ActorRef, ActorRef)>>,
 }
 
 impl InnerActorSystem {
@@ -249,41 +472,48 @@ impl InnerActorSystem {
             user_actor: RwLock::new(None),
             system_actor: RwLock::new(None),
             name_resolver: RwLock::new(None),
+            scheduler: Scheduler::new(),
+            registry: Registry::new(),
+            event_stream: EventStream::new(),
+            last_uid: AtomicU64::new(0),
+            distant_monitoring: Mutex::new(Vec::new()),
         }
     }
 
     /// Spawns an Actor for the user with the given ActorFactory.
     ///
-    /// This will be part of the user cator hierarchy.
-    fn actor_of(&self, props: Arc<ActorFactory>, name: String) -> ActorRef {
+    /// This will be part of the user cator hierarchy, the actor is anonymous if no name is given.
+    fn actor_of(&self, props: Arc<ActorFactory>, name: Option<String>) -> Result<ActorRef, RobotsError> {
         // Not having the user actor in a Mutex is ok because the actor_of function already has
         // mutual exclusion, so we are in the clear.
         match self.user_actor.read().unwrap().clone() {
             Some(user_actor) => {
                 // NOTE: this creates a lot of things but this is not meant to be used outside of
                 // the initialisation of the system so this is fine by my book.
-                let (tx, rx) = channel::<Result<ActorRef, &'static str>>();
+                let (tx, rx) = channel::<Result<ActorRef, RobotsError>>();
                 info!("Created the channel to get an ActorRef from a root actor");
                 self.cthulhu.read().unwrap().as_ref().unwrap().tell_to(user_actor, (props, name, Arc::new(Mutex::new(tx))));
-                rx.recv().unwrap().unwrap()
+                // The root actor can be dropped without answering if the system is shut down.
+                rx.recv().unwrap_or(Err(RobotsError::SystemShutdown))
             },
-            None => panic!("The user actor is not initialised"),
+            None => Err(RobotsError::SystemShutdown),
         }
     }
 
-    fn system_actor_of(&self, props: Arc<ActorFactory>, name: String) -> ActorRef {
+    fn system_actor_of(&self, props: Arc<ActorFactory>, name: Option<String>) -> Result<ActorRef, RobotsError> {
         // Not having the user actor in a Mutex is ok because the actor_of function already has
         // mutual exclusion, so we are in the clear.
         match self.system_actor.read().unwrap().clone() {
             Some(system_actor) => {
                 // NOTE: this creates a lot of things but this is not meant to be used outside of
                 // the initialisation of the system so this is fine by my book.
-                let (tx, rx) = channel::<Result<ActorRef, &'static str>>();
+                let (tx, rx) = channel::<Result<ActorRef, RobotsError>>();
                 info!("Created the channel to get an ActorRef from a root actor");
                 self.cthulhu.read().unwrap().as_ref().unwrap().tell_to(system_actor, (props, name, Arc::new(Mutex::new(tx))));
-                rx.recv().unwrap().unwrap()
+                // The root actor can be dropped without answering if the system is shut down.
+                rx.recv().unwrap_or(Err(RobotsError::SystemShutdown))
             },
-            None => panic!("The system actor is not initialised"),
+            None => Err(RobotsError::SystemShutdown),
         }
     }
 
@@ -293,6 +523,7 @@ impl InnerActorSystem {
         // n_thread.
         let n = {*self.n_threads.lock().unwrap()};
         self.terminate_threads(n);
+        self.scheduler.stop();
         *self.user_actor.write().unwrap() = None;
         *self.system_actor.write().unwrap() = None;
         *self.cthulhu.write().unwrap() = None;
@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use actors::{ActorCell, ActorContext, ActorRef, Message, RobotsError};
+
+/// A message that was sent but not confirmed yet.
+struct Unconfirmed {
+    recipient: ActorRef,
+    // Sends the message again to the recipient.
+    send: Box<Fn(&ActorCell, ActorRef) + Send>,
+    last_sent: Instant,
+}
+
+struct Deliveries {
+    next_delivery_id: u64,
+    unconfirmed: BTreeMap<u64, Unconfirmed>,
+    // Whether a redelivery is scheduled.
+    scheduled: bool,
+}
+
+/// Helper for actors which need their messages to be received at least once.
+///
+/// An actor keeps an AtLeastOnceDelivery and sends its messages with `deliver`, which gives each
+/// message a delivery id. The message is sent again every `redeliver_interval` until the actor
+/// calls `confirm` with its delivery id, which is usually done when the recipient answers with it.
+/// Recipients may thus receive a message several times.
+///
+/// The unconfirmed messages are lost when the actor is restarted or stopped.
+pub struct AtLeastOnceDelivery {
+    redeliver_interval: Duration,
+    max_unconfirmed: usize,
+    // The redelivery tasks only keep a weak reference to the deliveries, so that they stop with the
+    // actor instance.
+    deliveries: Arc<Mutex<Deliveries>>,
+}
+
+impl AtLeastOnceDelivery {
+    /// Creates an AtLeastOnceDelivery redelivering the unconfirmed messages every
+    /// `redeliver_interval`, and refusing to send new messages while `max_unconfirmed` are not
+    /// confirmed.
+    pub fn new(redeliver_interval: Duration, max_unconfirmed: usize) -> AtLeastOnceDelivery {
+        AtLeastOnceDelivery {
+            redeliver_interval: redeliver_interval,
+            max_unconfirmed: max_unconfirmed,
+            deliveries: Arc::new(Mutex::new(Deliveries {
+                next_delivery_id: 1,
+                unconfirmed: BTreeMap::new(),
+                scheduled: false,
+            })),
+        }
+    }
+
+    /// Sends the message built with its delivery id to the recipient with `ActorContext::tell`,
+    /// and gives the delivery id.
+    ///
+    /// This fails with `RobotsError::TooManyUnconfirmed` if the maximum number of unconfirmed
+    /// messages is reached, the message is then not sent.
+    pub fn deliver<M, F>(&self, context: &ActorCell, recipient: ActorRef, message: F) -> Result<u64, RobotsError>
+        where M: Message,
+              F: FnOnce(u64) -> M
+    {
+        let mut deliveries = self.deliveries.lock().unwrap();
+        if deliveries.unconfirmed.len() >= self.max_unconfirmed {
+            return Err(RobotsError::TooManyUnconfirmed(self.max_unconfirmed));
+        }
+        let delivery_id = deliveries.next_delivery_id;
+        deliveries.next_delivery_id += 1;
+        let message = message(delivery_id);
+        context.tell(recipient.clone(), message.clone());
+        deliveries.unconfirmed.insert(delivery_id, Unconfirmed {
+            recipient: recipient,
+            send: Box::new(move |context, recipient| context.tell(recipient, message.clone())),
+            last_sent: Instant::now(),
+        });
+        if !deliveries.scheduled {
+            deliveries.scheduled = true;
+            self.schedule_redelivery(context);
+        }
+        Ok(delivery_id)
+    }
+
+    /// Confirms the message with the given delivery id, which will not be sent again.
+    ///
+    /// Returns false if there was no unconfirmed message with this id (if it was already confirmed
+    /// for example).
+    pub fn confirm(&self, delivery_id: u64) -> bool {
+        self.deliveries.lock().unwrap().unconfirmed.remove(&delivery_id).is_some()
+    }
+
+    /// Number of messages that are not confirmed yet.
+    pub fn unconfirmed_count(&self) -> usize {
+        self.deliveries.lock().unwrap().unconfirmed.len()
+    }
+
+    /// Delivery ids and recipients of the messages that are not confirmed yet, by delivery id.
+    pub fn unconfirmed(&self) -> Vec<(u64, ActorRef)> {
+        let deliveries = self.deliveries.lock().unwrap();
+        deliveries.unconfirmed.iter().map(|(id, unconfirmed)| (*id, unconfirmed.recipient.clone())).collect()
+    }
+
+    /// Maximum number of unconfirmed messages.
+    pub fn max_unconfirmed(&self) -> usize {
+        self.max_unconfirmed
+    }
+
+    fn schedule_redelivery(&self, context: &ActorCell) {
+        AtLeastOnceDelivery::schedule(Arc::downgrade(&self.deliveries), context.clone(), self.redeliver_interval);
+    }
+
+    fn schedule(deliveries: Weak<Mutex<Deliveries>>, context: ActorCell, interval: Duration) {
+        let task_context = context.clone();
+        context.schedule(interval, Box::new(move || {
+            AtLeastOnceDelivery::redeliver(deliveries.clone(), task_context.clone(), interval);
+        }));
+    }
+
+    /// Sends again the messages which were not confirmed during the interval, and schedules the
+    /// next redelivery if some messages are still not confirmed.
+    fn redeliver(deliveries: Weak<Mutex<Deliveries>>, context: ActorCell, interval: Duration) {
+        let strong_deliveries = match deliveries.upgrade() {
+            Some(deliveries) => deliveries,
+            None => return,
+        };
+        if !context.is_alive() {
+            return;
+        }
+        let mut state = strong_deliveries.lock().unwrap();
+        let now = Instant::now();
+        for unconfirmed in state.unconfirmed.values_mut() {
+            if now.duration_since(unconfirmed.last_sent) >= interval {
+                (unconfirmed.send)(&context, unconfirmed.recipient.clone());
+                unconfirmed.last_sent = now;
+            }
+        }
+        if state.unconfirmed.is_empty() {
+            state.scheduled = false;
+        } else {
+            AtLeastOnceDelivery::schedule(deliveries, context, interval);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use actors::future::FutureFailure;
+
+/// Errors returned by the public API of RobotS.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RobotsError {
+    /// The name given to an actor is not valid, it can't contain a `/` nor start with a `$`.
+    InvalidName(String),
+    /// An actor with this logical path already exists, or is still terminating.
+    NameTaken(String),
+    /// No actor has this logical path.
+    ActorNotFound(String),
+    /// The actor with this logical path is stopped.
+    ActorStopped(String),
+    /// The actor system was shut down.
+    SystemShutdown,
+    /// The operation was not done before its deadline.
+    Timeout,
+    /// A Future failed for another reason than a timeout.
+    FutureFailed(FutureFailure),
+    /// An actor failed with the given reason.
+    Failed(String),
+    /// An actor panicked while handling a message.
+    Panicked,
+    /// A value could not be serialized or deserialized.
+    Serialization(String),
+    /// Reading or writing the storage (such as a journal) failed.
+    Storage(String),
+    /// The given maximum number of unconfirmed messages was reached.
+    TooManyUnconfirmed(usize),
+    /// Communicating with another node failed.
+    Network(String),
+    /// The node with the given address can't be reached.
+    Unreachable(String),
+}
+
+impl RobotsError {
+    fn message(&self) -> &'static str {
+        match *self {
+            RobotsError::InvalidName(_) => "the name of the actor is not valid",
+            RobotsError::NameTaken(_) => "an actor with this path already exists",
+            RobotsError::ActorNotFound(_) => "no actor has this path",
+            RobotsError::ActorStopped(_) => "the actor is stopped",
+            RobotsError::SystemShutdown => "the actor system was shut down",
+            RobotsError::Timeout => "the operation was not done before its deadline",
+            RobotsError::FutureFailed(_) => "the Future failed",
+            RobotsError::Failed(_) => "the actor failed",
+            RobotsError::Panicked => "the actor panicked",
+            RobotsError::Serialization(_) => "the value could not be serialized",
+            RobotsError::Storage(_) => "the storage failed",
+            RobotsError::TooManyUnconfirmed(_) => "too many messages are not confirmed",
+            RobotsError::Network(_) => "the network failed",
+            RobotsError::Unreachable(_) => "the node can't be reached",
+        }
+    }
+}
+
+impl fmt::Display for RobotsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RobotsError::InvalidName(ref name) => write!(f, "{}: {}", self.message(), name),
+            RobotsError::NameTaken(ref path) |
+            RobotsError::ActorNotFound(ref path) |
+            RobotsError::ActorStopped(ref path) => write!(f, "{}: {}", self.message(), path),
+            RobotsError::Unreachable(ref address) => write!(f, "{}: {}", self.message(), address),
+            RobotsError::FutureFailed(ref failure) => write!(f, "{}: {}", self.message(), failure),
+            RobotsError::Failed(ref reason) |
+            RobotsError::Serialization(ref reason) |
+            RobotsError::Storage(ref reason) |
+            RobotsError::Network(ref reason) => write!(f, "{}: {}", self.message(), reason),
+            RobotsError::TooManyUnconfirmed(max) => write!(f, "{}: {} at most", self.message(), max),
+            _ => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+impl Error for RobotsError {
+    fn description(&self) -> &str {
+        self.message()
+    }
+}
+
+impl From<FutureFailure> for RobotsError {
+    fn from(failure: FutureFailure) -> RobotsError {
+        match failure {
+            FutureFailure::Timeout(_) => RobotsError::Timeout,
+            failure => RobotsError::FutureFailed(failure),
+        }
+    }
+}
+
+impl From<io::Error> for RobotsError {
+    fn from(error: io::Error) -> RobotsError {
+        RobotsError::Storage(error.to_string())
+    }
+}
+
+impl<'a> From<&'a str> for RobotsError {
+    fn from(reason: &'a str) -> RobotsError {
+        RobotsError::Failed(reason.to_owned())
+    }
+}
+
+impl From<String> for RobotsError {
+    fn from(reason: String) -> RobotsError {
+        RobotsError::Failed(reason)
+    }
+}
@@ -0,0 +1,195 @@
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use actors::{ActorPath, ActorRef, Delivery, InnerMessage, Message, RobotsError};
+
+/// Class of the events published by the actor system (`ActorStarted`, `ActorStopped`,
+/// `ActorFailed` and `DeadLetter`), subscribing to `TypeId::of::<SystemEvent>()` gives all of them.
+#[derive(Clone, Copy)]
+pub enum SystemEvent {}
+
+/// Published when an actor is started.
+#[derive(Clone)]
+pub struct ActorStarted {
+    /// The actor that was started.
+    pub actor: ActorRef,
+}
+
+/// Published when an actor is dropped.
+#[derive(Clone)]
+pub struct ActorStopped {
+    /// Logical path of the actor.
+    pub path: Arc<ActorPath>,
+    /// Uid of the incarnation of the actor that was dropped.
+    pub uid: u64,
+}
+
+/// Published when an actor fails.
+#[derive(Clone)]
+pub struct ActorFailed {
+    /// The actor that failed.
+    pub actor: ActorRef,
+    /// Reason of the failure.
+    pub reason: RobotsError,
+}
+
+/// Published when a message sent by an actor or the actor system could not be delivered.
+#[derive(Clone)]
+pub struct DeadLetter {
+    /// The actor the message was sent to.
+    pub recipient: ActorRef,
+    /// The sender of the message.
+    pub sender: ActorRef,
+}
+
+/// Publish/subscribe bus of the actor system.
+///
+/// Actors subscribe to a class of events given by a `TypeId`, they then get every published event
+/// of that type, or of a type classified in that class with `add_class`. Events are published by
+/// the actor system (see `SystemEvent`) and by any code having the actor system.
+///
+/// Subscriptions of stopped actors are removed.
+pub struct EventStream {
+    subscribers: RwLock<HashMap<TypeId, Vec<ActorRef>>>,
+    // Classes of each type of event, besides the type itself.
+    classes: RwLock<HashMap<TypeId, Vec<TypeId>>>,
+}
+
+impl EventStream {
+    /// Creates an EventStream where the system events are classified as `SystemEvent`.
+    pub(crate) fn new() -> EventStream {
+        let event_stream = EventStream {
+            subscribers: RwLock::new(HashMap::new()),
+            classes: RwLock::new(HashMap::new()),
+        };
+        let system_event = TypeId::of::<SystemEvent>();
+        event_stream.add_class(TypeId::of::<ActorStarted>(), system_event);
+        event_stream.add_class(TypeId::of::<ActorStopped>(), system_event);
+        event_stream.add_class(TypeId::of::<ActorFailed>(), system_event);
+        event_stream.add_class(TypeId::of::<DeadLetter>(), system_event);
+        event_stream
+    }
+
+    /// Subscribes the actor to the events of the given class, subscribing twice does nothing.
+    pub fn subscribe(&self, subscriber: ActorRef, class: TypeId) {
+        let mut subscribers = self.subscribers.write().unwrap();
+        let subscribers = subscribers.entry(class).or_default();
+        if !subscribers.iter().any(|other| same_actor(other, &subscriber)) {
+            subscribers.push(subscriber);
+        }
+    }
+
+    /// Unsubscribes the actor from the events of the given class.
+    pub fn unsubscribe(&self, subscriber: &ActorRef, class: TypeId) {
+        let mut subscribers = self.subscribers.write().unwrap();
+        if let Some(subscribers) = subscribers.get_mut(&class) {
+            subscribers.retain(|other| !same_actor(other, subscriber));
+        }
+    }
+
+    /// Unsubscribes the actor from all the classes of events.
+    pub fn unsubscribe_all(&self, subscriber: &ActorRef) {
+        let mut subscribers = self.subscribers.write().unwrap();
+        for subscribers in subscribers.values_mut() {
+            subscribers.retain(|other| !same_actor(other, subscriber));
+        }
+    }
+
+    /// Classifies the events of type `event` in the given class, they are then also sent to the
+    /// subscribers of this class. Classes can themselves be classified in other classes.
+    pub fn add_class(&self, event: TypeId, class: TypeId) {
+        let mut classes = self.classes.write().unwrap();
+        let classes = classes.entry(event).or_default();
+        if !classes.contains(&class) {
+            classes.push(class);
+        }
+    }
+
+    /// Sends the event to the subscribers of its type and of its classes, each subscriber gets it
+    /// once.
+    ///
+    /// The sender of the event is a dead letters ActorRef, so answers are dropped.
+    pub fn publish<M: Message>(&self, event: M) {
+        self.publish_from(event, ActorRef::dead_letters());
+    }
+
+    /// Same as `publish`, with the given sender.
+    pub(crate) fn publish_from<M: Message>(&self, event: M, sender: ActorRef) {
+        let classes = self.classes_of(TypeId::of::<M>());
+        let mut recipients: Vec<ActorRef> = Vec::new();
+        {
+            let subscribers = self.subscribers.read().unwrap();
+            for class in classes.iter() {
+                for subscriber in subscribers.get(class).into_iter().flat_map(|subscribers| subscribers.iter()) {
+                    if !recipients.iter().any(|other| same_actor(other, subscriber)) {
+                        recipients.push(subscriber.clone());
+                    }
+                }
+            }
+        }
+        let mut dead = false;
+        for recipient in recipients {
+            let message = InnerMessage::Message(Box::new(event.clone()));
+            dead |= recipient.receive(message, sender.clone()) == Delivery::DeadLetter;
+        }
+        if dead {
+            self.remove_dead();
+        }
+    }
+
+    /// Removes the subscriptions of the actor incarnation with the given uid.
+    pub(crate) fn remove_subscriber(&self, uid: u64) {
+        let subscribed = {
+            let subscribers = self.subscribers.read().unwrap();
+            subscribers.values().any(|subscribers| subscribers.iter().any(|subscriber| subscriber.uid() == uid))
+        };
+        if subscribed {
+            let mut subscribers = self.subscribers.write().unwrap();
+            for subscribers in subscribers.values_mut() {
+                subscribers.retain(|subscriber| subscriber.uid() != uid);
+            }
+        }
+    }
+
+    /// Removes the subscriptions of the actors that are no longer alive.
+    ///
+    /// Checking whether an actor is alive may drop it, so it is done without holding the lock.
+    fn remove_dead(&self) {
+        let subscribed: Vec<ActorRef> = self.subscribers.read().unwrap()
+                                            .values()
+                                            .flat_map(|subscribers| subscribers.iter().cloned())
+                                            .collect();
+        let dead: Vec<u64> = subscribed.iter()
+                                       .filter(|subscriber| !subscriber.is_alive())
+                                       .map(|subscriber| subscriber.uid())
+                                       .collect();
+        if dead.is_empty() {
+            return;
+        }
+        let mut subscribers = self.subscribers.write().unwrap();
+        for subscribers in subscribers.values_mut() {
+            subscribers.retain(|subscriber| !dead.contains(&subscriber.uid()));
+        }
+    }
+
+    /// Gives the type with all its classes, and their own classes.
+    fn classes_of(&self, event: TypeId) -> Vec<TypeId> {
+        let classes = self.classes.read().unwrap();
+        let mut all = vec![event];
+        let mut i = 0;
+        while i < all.len() {
+            for class in classes.get(&all[i]).into_iter().flat_map(|classes| classes.iter()) {
+                if !all.contains(class) {
+                    all.push(*class);
+                }
+            }
+            i += 1;
+        }
+        all
+    }
+}
+
+fn same_actor(first: &ActorRef, second: &ActorRef) -> bool {
+    first.uid() == second.uid() && first.path() == second.path()
+}
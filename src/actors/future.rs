@@ -1,32 +1,126 @@
 use std::any::Any;
 use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::future::Future as StdFuture;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
 use std::sync::{Arc, Mutex};
-use std::sync::mpsc::Sender;
+use std::task::{Context, Poll, Waker};
 
-use actors::{Actor, ActorCell, ActorContext, ActorRef, Message};
+use actors::{Actor, ActorCell, ActorContext, ActorPath, ActorRef, InnerMessage, Message, RobotsError};
 
 pub struct Complete {
-    complete: Box<Any + Send>,
+    complete: Result<Box<Any + Send>, FutureFailure>,
 }
 
 impl Complete {
-    pub fn new(complete: Box<Any + Send>) -> Complete {
+    pub fn new(complete: Result<Box<Any + Send>, FutureFailure>) -> Complete {
         Complete {
             complete: complete,
         }
     }
 }
 
+/// Message sent to a Future when the deadline given to an `ask` has passed.
+#[derive(Clone, Copy)]
+pub struct FutureTimeout;
+
+/// Error given when a Future was not completed before the deadline given to `ask_timeout`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AskTimeout;
+
+impl fmt::Display for AskTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the Future was not completed before its deadline")
+    }
+}
+
+impl Error for AskTimeout {
+    fn description(&self) -> &str {
+        "the Future was not completed before its deadline"
+    }
+}
+
+/// Reasons for which a Future failed.
+///
+/// A failed Future skips the computations it is given and sends its failure to whoever its result
+/// is forwarded to, in place of the expected value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FutureFailure {
+    /// The Future was not completed before its deadline.
+    Timeout(AskTimeout),
+    /// The value of the Future was not of the type expected by the receiver of the result.
+    WrongType,
+    /// A computation done on the value of the Future panicked.
+    Panicked,
+    /// The result of the Future was already forwarded to someone else.
+    Extracted,
+}
+
+impl From<AskTimeout> for FutureFailure {
+    fn from(timeout: AskTimeout) -> FutureFailure {
+        FutureFailure::Timeout(timeout)
+    }
+}
+
+impl FutureFailure {
+    fn message(&self) -> &'static str {
+        match *self {
+            FutureFailure::Timeout(_) => "the Future was not completed before its deadline",
+            FutureFailure::WrongType => "the value of the Future was not of the expected type",
+            FutureFailure::Panicked => "a computation on the value of the Future panicked",
+            FutureFailure::Extracted => "the result of the Future was already forwarded",
+        }
+    }
+}
+
+impl fmt::Display for FutureFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl Error for FutureFailure {
+    fn description(&self) -> &str {
+        self.message()
+    }
+}
+
 #[derive(Clone)]
 pub enum Computation {
-    Forward(ActorRef, Arc<Fn(Box<Any + Send>, ActorCell, ActorRef) -> FutureState + Send + Sync>),
+    Forward(ActorRef, Arc<Fn(Result<Box<Any + Send>, FutureFailure>, ActorCell, ActorRef) -> FutureState + Send + Sync>),
     // This is a terrible name.
     Computation(Arc<Fn(Box<Any + Send>, ActorCell) -> FutureState + Send + Sync>),
+    // The value is replaced by the one of the Future returned by the closure.
+    FlatMap(Arc<Fn(Box<Any + Send>, ActorCell) -> Result<ActorRef, FutureFailure> + Send + Sync>),
+}
+
+/// Completes the `to` Future with the given result, whatever its type.
+pub fn complete_future(context: &ActorCell, to: ActorRef, result: Result<Box<Any + Send>, FutureFailure>) {
+    let path = to.path();
+    match *path {
+        ActorPath::Local(_) => {
+            to.receive(InnerMessage::Message(Box::new(Complete::new(result))), context.actor_ref());
+        },
+        ActorPath::Distant(ref path) => {
+            info!("Sent a result to distant future {}:{}", path.distant_logical_path(), path.addr_port());
+        },
+    }
+}
+
+/// Computation forwarding the result of a Future to the `to` Future, whatever its type.
+pub fn forward_to_future(to: ActorRef) -> Computation {
+    Computation::Forward(to, Arc::new(|result, context, to| {
+        complete_future(&context, to, result);
+        FutureState::Extracted
+    }))
 }
 
 pub enum FutureState {
     Uncompleted,
     Computing(Box<Any + Send>),
+    Failed(FutureFailure),
     Extracted,
 }
 
@@ -43,28 +137,109 @@ impl Future {
         }
     }
 
+    /// Completes the future with the given result and does the scheduled computations.
+    fn complete(&self, result: Result<Box<Any + Send>, FutureFailure>, context: ActorCell) {
+        // We need to free the lock on the state.
+        {
+            let mut state = self.state.lock().unwrap();
+            let s = state.take().unwrap();
+            match s {
+                FutureState::Uncompleted => {
+                    *state = Some(match result {
+                        Ok(value) => FutureState::Computing(value),
+                        Err(failure) => FutureState::Failed(failure),
+                    });
+                    info!("{} has been completed", context.actor_ref().path().logical_path());
+                },
+                _ => {
+                    // This happens when the answer to a Future comes after its deadline, or when
+                    // only the first of several Futures is expected, the first result is kept.
+                    *state = Some(s);
+                    info!("Tried to complete {} twice, the new result is dropped", context.actor_ref().path().logical_path());
+                    return;
+                },
+            }
+        }
+
+        // We now do the previous scheduled computations, unless one of them made us wait for the
+        // value of another Future.
+        loop {
+            if let Some(FutureState::Uncompleted) = *self.state.lock().unwrap() {
+                break;
+            }
+            // The lock is released before handling the computation, as it may need it.
+            let func = self.scheduled_calculations.lock().unwrap().pop_front();
+            match func {
+                Some(func) => self.handle_computation(func, context.clone()),
+                None => break,
+            }
+        }
+    }
+
+    /// Fails the future with an AskTimeout if it has not been completed yet.
+    fn time_out(&self, context: ActorCell) {
+        {
+            let state = self.state.lock().unwrap();
+            match *state {
+                Some(FutureState::Uncompleted) => {},
+                _ => return,
+            }
+        }
+        info!("{} timed out", context.actor_ref().path().logical_path());
+        self.complete(Err(FutureFailure::Timeout(AskTimeout)), context);
+    }
+
     fn handle_computation(&self, computation: Computation, context: ActorCell) {
         info!("{} handling a computation", context.actor_ref().path().logical_path());
         let mut state = self.state.lock().unwrap();
         let s = state.take().unwrap();
         match s {
             FutureState::Computing(value) => {
-                match computation {
-                    Computation::Forward(to, func) => {
-                        info!("{} forwarding to {}", context.actor_ref().path().logical_path(), to.path().logical_path());
-                        *state = Some((*func)(value, context.clone(), to));
-                    },
-                    Computation::Computation(func) => {
-                        *state = Some((*func)(value, context.clone()));
+                // The closures are given by the users, so we do not want them to take the Future
+                // down with them if they panic.
+                let new_state = panic::catch_unwind(AssertUnwindSafe(|| {
+                    match computation {
+                        Computation::Forward(to, func) => {
+                            info!("{} forwarding to {}", context.actor_ref().path().logical_path(), to.path().logical_path());
+                            (*func)(Ok(value), context.clone(), to)
+                        },
+                        Computation::Computation(func) => (*func)(value, context.clone()),
+                        Computation::FlatMap(func) => {
+                            match (*func)(value, context.clone()) {
+                                Ok(future) => {
+                                    // We go back to waiting for a value, the one of the new Future.
+                                    context.tell(future, forward_to_future(context.actor_ref()));
+                                    FutureState::Uncompleted
+                                },
+                                Err(failure) => FutureState::Failed(failure),
+                            }
+                        },
                     }
-                }
+                }));
+                *state = Some(new_state.unwrap_or(FutureState::Failed(FutureFailure::Panicked)));
                 match *state {
-                    Some(FutureState::Computing(_)) => {},
+                    Some(FutureState::Computing(_)) | Some(FutureState::Failed(_)) => {},
                     Some(FutureState::Extracted) => context.kill_me(),
-                    Some(FutureState::Uncompleted) => panic!("A future closure returned Uncompleted, this should not happen"),
+                    Some(FutureState::Uncompleted) => {
+                        info!("{} is waiting for the value of another Future", context.actor_ref().path().logical_path());
+                    },
                     None => unreachable!(),
                 }
-            }
+            },
+            FutureState::Failed(failure) => {
+                match computation {
+                    Computation::Forward(to, func) => {
+                        info!("{} forwarding its failure to {}", context.actor_ref().path().logical_path(), to.path().logical_path());
+                        (*func)(Err(failure), context.clone(), to);
+                        *state = Some(FutureState::Extracted);
+                        context.kill_me();
+                    },
+                    // The computation can't be done, the failure is kept for the receiver.
+                    Computation::Computation(_) | Computation::FlatMap(_) => {
+                        *state = Some(FutureState::Failed(failure));
+                    },
+                }
+            },
             // In the next cases we put the state back to how it was.
             FutureState::Uncompleted => {
                 *state = Some(s);
@@ -73,7 +248,10 @@ impl Future {
             },
             FutureState::Extracted => {
                 *state = Some(s);
-                panic!("A closure was called on an Extracted Future.");
+                warn!("A closure was sent to {} which was already extracted", context.actor_ref().path().logical_path());
+                if let Computation::Forward(to, func) = computation {
+                    (*func)(Err(FutureFailure::Extracted), context.clone(), to);
+                }
             },
         }
     }
@@ -88,49 +266,35 @@ impl Actor for Future {
             Err(message) => {
                 // The double downgrade is ugly but we can't have an enum containing the two
                 // variants (see http://gamazeps.github.io/posts/robots_notes_7.html).
-                if let Ok(msg) = Box::<Any>::downcast::<Complete>(message) {
-                    // We need to free the lock on the state.
-                    {
-                        let mut state = self.state.lock().unwrap();
-                        let s = state.take().unwrap();
-                        match s {
-                            FutureState::Uncompleted => {
-                                // We complete the future with the received value, an unsafe cast is
-                                // nevertheless needed to get a Box<Any + Send>
-                                *state = Some(FutureState::Computing((*msg).complete));
-                                info!("{} has been completed", context.actor_ref().path().logical_path());
-                            },
-                            _ => {
-                                // NOTE: Send a failure to the sender instead.
-                                panic!("Tried to complete a {} twice", context.actor_ref().path().logical_path());
-                            },
+                match Box::<Any>::downcast::<Complete>(message) {
+                    Ok(msg) => self.complete((*msg).complete, context),
+                    Err(message) => {
+                        if message.is::<FutureTimeout>() {
+                            self.time_out(context);
+                        } else {
+                            info!("{} received a message of the wrong format from {}", context.actor_ref().path().logical_path(),
+                            context.sender().path().logical_path());
                         }
-                    }
-
-                    // We now do the previous scheduled computations.
-                    let mut scheduled_calculations = self.scheduled_calculations.lock().unwrap();
-                    while let Some(func) = scheduled_calculations.pop_front() {
-                        self.handle_computation(func, context.clone());
-                    }
-                } else {
-                    info!("{} received a message of the wrong format from {}", context.actor_ref().path().logical_path(),
-                    context.sender().path().logical_path());
+                    },
                 }
             }
         }
     }
 }
 
+/// Closure called by a FutureExtractor with the result of its Future.
+pub type ExtractorCallback<T> = Arc<Fn(Result<T, FutureFailure>) + Send + Sync>;
+
 pub struct FutureExtractor<T: Message> {
     future: ActorRef,
-    channel: Arc<Mutex<Sender<T>>>,
+    callback: ExtractorCallback<T>,
 }
 
 impl<T: Message> FutureExtractor<T> {
-    pub fn new(args: (ActorRef, Arc<Mutex<Sender<T>>>)) -> FutureExtractor<T> {
+    pub fn new(args: (ActorRef, ExtractorCallback<T>)) -> FutureExtractor<T> {
         FutureExtractor {
             future: args.0,
-            channel: args.1,
+            callback: args.1,
         }
     }
 }
@@ -141,14 +305,164 @@ impl<T: Message> Actor for FutureExtractor<T> {
         context.forward_result::<T>(self.future.clone(), context.actor_ref());
     }
 
-    // It then receives the result and will send it through its channel.
+    // It then receives the result and will give it to its callback.
+    fn receive(&self, message: Box<Any>, context: ActorCell) {
+        let result = match Box::<Any>::downcast::<T>(message) {
+            Ok(message) => {
+                info!("The extractor {} received the type it wants to extract", context.actor_ref().path().logical_path());
+                Ok(*message)
+            },
+            Err(message) => {
+                match Box::<Any>::downcast::<FutureFailure>(message) {
+                    Ok(failure) => {
+                        info!("The extractor {} received a failure", context.actor_ref().path().logical_path());
+                        Err(*failure)
+                    },
+                    Err(_) => return,
+                }
+            },
+        };
+        (*self.callback)(result);
+        // Once we have given the result to the callback, we want this actor to be dropped.
+        context.kill_me();
+    }
+}
+
+struct AsyncSlot<T> {
+    result: Option<Result<T, FutureFailure>>,
+    waker: Option<Waker>,
+}
+
+/// Result of a RobotS Future, usable with `.await` by asynchronous code outside of the actors.
+///
+/// It is created with `ActorSystem::async_result`, and is woken up when the Future gives its
+/// result.
+pub struct AsyncResult<T: Message> {
+    slot: Arc<Mutex<AsyncSlot<T>>>,
+}
+
+impl<T: Message> AsyncResult<T> {
+    /// Creates an AsyncResult and the callback to give to the FutureExtractor completing it.
+    pub fn new() -> (AsyncResult<T>, ExtractorCallback<T>) {
+        let slot = Arc::new(Mutex::new(AsyncSlot {
+            result: None,
+            waker: None,
+        }));
+        let extractor_slot = slot.clone();
+        let callback = Arc::new(move |result| {
+            let mut slot = extractor_slot.lock().unwrap();
+            slot.result = Some(result);
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+        });
+        (AsyncResult { slot: slot }, callback)
+    }
+}
+
+impl<T: Message> StdFuture for AsyncResult<T> {
+    type Output = Result<T, RobotsError>;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context) -> Poll<Result<T, RobotsError>> {
+        let mut slot = self.slot.lock().unwrap();
+        match slot.result.take() {
+            Some(result) => Poll::Ready(result.map_err(RobotsError::from)),
+            None => {
+                slot.waker = Some(context.waker().clone());
+                Poll::Pending
+            },
+        }
+    }
+}
+
+/// Message sent to a Gather actor with the result of one of the Futures it gathers.
+pub struct GatherItem {
+    index: usize,
+    result: Result<Box<Any + Send>, FutureFailure>,
+}
+
+/// Closure building the final value of a Gather actor from the values of the gathered Futures.
+pub type GatherFinisher = Arc<Fn(Vec<Box<Any + Send>>) -> Result<Box<Any + Send>, FutureFailure> + Send + Sync>;
+
+/// Actor collecting the results of several Futures in order to complete another Future with them.
+///
+/// The first failure of a gathered Future fails the completed Future.
+pub struct Gather {
+    slots: Mutex<Vec<Option<Box<Any + Send>>>>,
+    result: ActorRef,
+    finish: GatherFinisher,
+    done: Mutex<bool>,
+}
+
+impl Gather {
+    pub fn new(args: (usize, ActorRef, GatherFinisher)) -> Gather {
+        let (n_futures, result, finish) = args;
+        Gather {
+            slots: Mutex::new((0..n_futures).map(|_| None).collect()),
+            result: result,
+            finish: finish,
+            done: Mutex::new(false),
+        }
+    }
+
+    /// Gives the Computation making a Future send its result to the gather actor, as the item with
+    /// the given index.
+    pub fn forward_item(gather: ActorRef, index: usize) -> Computation {
+        Computation::Forward(gather, Arc::new(move |result, context, to| {
+            let item = GatherItem {
+                index: index,
+                result: result,
+            };
+            to.receive(InnerMessage::Message(Box::new(item)), context.actor_ref());
+            FutureState::Extracted
+        }))
+    }
+
+    /// Completes the result Future and stops the actor.
+    fn finish(&self, result: Result<Box<Any + Send>, FutureFailure>, context: ActorCell) {
+        *self.done.lock().unwrap() = true;
+        complete_future(&context, self.result.clone(), result);
+        context.kill_me();
+    }
+
+    fn try_finish(&self, context: ActorCell) {
+        let values = {
+            let mut slots = self.slots.lock().unwrap();
+            if slots.iter().any(|slot| slot.is_none()) {
+                return;
+            }
+            slots.drain(..).map(|slot| slot.unwrap()).collect()
+        };
+        let result = match panic::catch_unwind(AssertUnwindSafe(|| (*self.finish)(values))) {
+            Ok(result) => result,
+            Err(_) => Err(FutureFailure::Panicked),
+        };
+        self.finish(result, context);
+    }
+}
+
+impl Actor for Gather {
+    // Nothing needs to be gathered for an empty list of Futures.
+    fn pre_start(&self, context: ActorCell) {
+        self.try_finish(context);
+    }
+
     fn receive(&self, message: Box<Any>, context: ActorCell) {
-        if let Ok(message) = Box::<Any>::downcast::<T>(message) {
-            info!("The extractor {} received the type it wants to extract", context.actor_ref().path().logical_path());
-            // FIXME(gamazeps): error handling.
-            let _res = self.channel.lock().unwrap().send(*message);
-            // Once we have sent the message through the channel, we want this actor to be dropped.
-            context.kill_me();
+        if let Ok(item) = Box::<Any>::downcast::<GatherItem>(message) {
+            if *self.done.lock().unwrap() {
+                return;
+            }
+            let item = *item;
+            match item.result {
+                Ok(value) => {
+                    self.slots.lock().unwrap()[item.index] = Some(value);
+                    self.try_finish(context);
+                },
+                Err(failure) => {
+                    info!("{} gathered a failure", context.actor_ref().path().logical_path());
+                    self.finish(Err(failure), context);
+                },
+            }
         }
     }
 }
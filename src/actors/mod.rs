@@ -1,9 +1,16 @@
 pub use std::any::Any;
 
 pub use self::actor_cell::{ActorCell, ActorContext, ControlMessage, InnerMessage, SystemMessage};
-pub use self::actor_ref::{ActorPath, ActorRef};
+pub use self::actor_ref::{ActorPath, ActorRef, Delivery};
 pub use self::actor_system::ActorSystem;
+pub use self::at_least_once_delivery::AtLeastOnceDelivery;
+pub use self::error::RobotsError;
+pub use self::event_stream::{ActorFailed, ActorStarted, ActorStopped, DeadLetter, EventStream, SystemEvent};
+pub use self::future::{AskTimeout, AsyncResult, FutureFailure};
+pub use self::name_resolver::ResolveRequest;
 pub use self::props::Props;
+pub use self::resizer::Resizer;
+pub use self::router::{Broadcast, HashKey, Router, RouterMessage, RoutingStrategy};
 
 /// Module for ActorRef and CanReceive, the interface given to the user to interract with  actors.
 pub mod actor_ref;
@@ -26,9 +33,30 @@ mod root_actor;
 /// Module with the name resolver actor.
 mod name_resolver;
 
+/// Module with the Routers, sending messages to pools of actors.
+mod router;
+
+/// Module with the Resizer, growing and shrinking the pools of Routers.
+mod resizer;
+
+/// Module with AtLeastOnceDelivery, redelivering messages until they are confirmed.
+mod at_least_once_delivery;
+
+/// Module with the registry of the actors by path.
+mod registry;
+
+/// Module with the EventStream, where actors subscribe to events.
+mod event_stream;
+
+/// Module with the errors returned by RobotS.
+mod error;
+
 /// Robots Future implementation.
 mod future;
 
+/// Module with the scheduler used to send messages after a delay.
+mod scheduler;
+
 /// Trait to be implemented by messages, this is automatically given if a struct is
 /// already `Clone + Send + Sync + 'static + Any`.
 pub trait Message: Clone + Send + Sync + 'static + Any {}
@@ -1,50 +1,49 @@
 use std::any::Any;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
-use actors::{Actor, ActorCell, ActorContext, ActorPath, ActorRef};
+use actors::{Actor, ActorCell, ActorContext, ActorRef, ActorSystem};
 
 /// Messages handled by the NameResolver.
 #[derive(Clone)]
 pub enum ResolveRequest {
-    /// Used when we create an actor.
-    Add(ActorRef),
-
-    /// Used when we delete an actor.
-    Remove(Arc<ActorPath>),
-
     /// Used when we want to find the actor associated to a path.
     Get(String),
+
+    /// Used when we want to find all the actors whose path matches a pattern.
+    Select(String),
+
+    /// Used when we want to send a message to all the actors whose path matches a pattern, the
+    /// closure does the sending.
+    Broadcast(String, Arc<Fn(ActorRef) + Send + Sync>),
 }
 
 /// Name resolving actor.
 ///
 /// It is used to resolve logical path to a real CanReceive.
 ///
-/// It accepts ResolveRequest as messages.
+/// It accepts ResolveRequest as messages, and answers them with the registry of the actor system
+/// which can also be read directly with `ActorSystem::lookup`.
 ///
-/// When an actor is created its father sends a registration request to the anme resolver it.
-/// When an actor terminates one of its children it send an unregistration request to the name
-/// resolver.
+/// The resolver holds no state, so it can be restarted without losing anything and actors do not
+/// need it to resolve paths.
 pub struct NameResolver {
-    index: Mutex<HashMap<Arc<ActorPath>, ActorRef>>,
+    actor_system: ActorSystem,
 }
 
 impl Actor for NameResolver {
     fn receive(&self, message: Box<Any>, context: ActorCell) {
         if let Ok(message) = Box::<Any>::downcast::<ResolveRequest>(message) {
             match *message {
-                ResolveRequest::Add(address) => {
-                    let mut index = self.index.lock().unwrap();
-                    index.insert(address.path(), address);
+                ResolveRequest::Get(address) => {
+                    context.complete(context.sender(), self.actor_system.lookup(&address));
                 }
-                ResolveRequest::Remove(address) => {
-                    let mut index = self.index.lock().unwrap();
-                    index.remove(&address);
+                ResolveRequest::Select(pattern) => {
+                    context.complete(context.sender(), self.actor_system.registry().select(&pattern));
                 }
-                ResolveRequest::Get(address) => {
-                    let index = self.index.lock().unwrap();
-                    context.complete(context.sender(), index.get(&ActorPath::new_local(address)).cloned());
+                ResolveRequest::Broadcast(pattern, send) => {
+                    for actor in self.actor_system.registry().select(&pattern) {
+                        (*send)(actor);
+                    }
                 }
             }
         }
@@ -52,7 +51,7 @@ impl Actor for NameResolver {
 }
 
 impl NameResolver {
-    pub fn new(_dummy: ()) -> NameResolver {
-        NameResolver { index: Mutex::new(HashMap::new()) }
+    pub fn new(actor_system: ActorSystem) -> NameResolver {
+        NameResolver { actor_system: actor_system }
     }
 }
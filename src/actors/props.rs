@@ -1,11 +1,17 @@
 use std::sync::Arc;
 
 use actors::{Actor, Arguments};
+use persistence::MailboxStorage;
 
 /// Public interface of a Props.
 pub trait ActorFactory: Send + Sync {
     /// Creates an Actor instance.
     fn create(&self) ->  Arc<Actor>;
+
+    /// Storage of the messages of the actors, if they have a durable mailbox.
+    fn mailbox_storage(&self) -> Option<Arc<MailboxStorage>> {
+        None
+    }
 }
 
 /// Props is the current only ActorFactory.
@@ -15,6 +21,7 @@ pub trait ActorFactory: Send + Sync {
 pub struct Props<Args: Arguments, A: Actor> {
     creator: Arc<Fn(Args) -> A + Sync + Send>,
     args: Args,
+    mailbox_storage: Option<Arc<MailboxStorage>>,
 }
 
 impl<Args: Arguments, A: Actor> Props<Args, A> {
@@ -23,6 +30,21 @@ impl<Args: Arguments, A: Actor> Props<Args, A> {
         Arc::new(Props::<Args, A> {
             creator: creator,
             args: args,
+            mailbox_storage: None,
+        })
+    }
+
+    /// Same as `new`, but the actors have a durable mailbox: their messages are written to the
+    /// `mailbox_storage` before being put in their mailbox, and the messages they did not handle
+    /// are given again to the next actor created with the same path, even by another process.
+    pub fn with_mailbox(creator: Arc<Fn(Args) -> A + Sync + Send>,
+                        args: Args,
+                        mailbox_storage: Arc<MailboxStorage>)
+                        -> Arc<ActorFactory> {
+        Arc::new(Props::<Args, A> {
+            creator: creator,
+            args: args,
+            mailbox_storage: Some(mailbox_storage),
         })
     }
 }
@@ -36,6 +58,10 @@ impl<Args: Arguments, A: Actor> ActorFactory for Props<Args, A> {
         let args = self.args.clone();
         Arc::new((self.creator)(args))
     }
+
+    fn mailbox_storage(&self) -> Option<Arc<MailboxStorage>> {
+        self.mailbox_storage.clone()
+    }
 }
 
 impl<Args: Arguments, A: Actor> Clone for Props<Args, A> {
@@ -43,6 +69,7 @@ impl<Args: Arguments, A: Actor> Clone for Props<Args, A> {
         Props::<Args, A> {
             creator: self.creator.clone(),
             args: self.args.clone(),
+            mailbox_storage: self.mailbox_storage.clone(),
         }
     }
 }
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::RwLock;
+
+use actors::ActorRef;
+
+/// Number of shards of the registry, a power of two.
+const SHARDS: usize = 16;
+
+/// Concurrent index of the living actors by logical path.
+///
+/// The index is split in shards, each behind its own RwLock. This is not lock-free: lookups take
+/// read locks, so they do not wait for each other, but a lookup blocks while an actor whose path
+/// falls in the same shard is registered or unregistered. Writers only hold a lock for a single
+/// map insertion or removal, so that wait is short.
+///
+/// Actors are registered by their father when they are created and unregistered when they are
+/// killed. Actors dropped along with their father are unregistered when they are dropped, until
+/// then lookups skip them as they are no longer alive.
+///
+/// Checking whether an actor is alive may drop it, and the drop unregisters the actor, so the
+/// shards are never locked while doing so.
+pub struct Registry {
+    shards: Vec<RwLock<HashMap<String, ActorRef>>>,
+}
+
+impl Registry {
+    /// Creates an empty Registry.
+    pub fn new() -> Registry {
+        Registry {
+            shards: (0..SHARDS).map(|_| RwLock::new(HashMap::new())).collect(),
+        }
+    }
+
+    fn shard(&self, path: &str) -> &RwLock<HashMap<String, ActorRef>> {
+        let mut hasher = DefaultHasher::new();
+        path.hash(&mut hasher);
+        &self.shards[(hasher.finish() as usize) & (SHARDS - 1)]
+    }
+
+    /// Registers an actor under its logical path, replacing the previous one.
+    pub fn insert(&self, actor: ActorRef) {
+        let path = actor.path().logical_path().clone();
+        self.shard(&path).write().unwrap().insert(path, actor);
+    }
+
+    /// Unregisters the actor with the given logical path.
+    pub fn remove(&self, path: &str) {
+        let _ = self.shard(path).write().unwrap().remove(path);
+    }
+
+    /// Unregisters the actor with the given logical path if it is no longer alive, so that an
+    /// actor that took its name in the meantime is kept.
+    pub fn remove_dead(&self, path: &str) {
+        let registered = self.shard(path).read().unwrap().get(path).cloned();
+        let dead = match registered {
+            Some(ref actor) if !actor.is_alive() => actor.uid(),
+            _ => return,
+        };
+        let mut shard = self.shard(path).write().unwrap();
+        if shard.get(path).map_or(false, |actor| actor.uid() == dead) {
+            let _ = shard.remove(path);
+        }
+    }
+
+    /// Gives the living actor with the given logical path, if any.
+    pub fn get(&self, path: &str) -> Option<ActorRef> {
+        let registered = self.shard(path).read().unwrap().get(path).cloned();
+        registered.filter(|actor| actor.is_alive())
+    }
+
+    /// Gives the living actors whose logical path matches the pattern, sorted by path.
+    ///
+    /// See `ActorContext::select` for the syntax of patterns.
+    pub fn select(&self, pattern: &str) -> Vec<ActorRef> {
+        let pattern: Vec<&str> = pattern.split('/').filter(|segment| !segment.is_empty()).collect();
+        let mut selection = Vec::new();
+        for shard in self.shards.iter() {
+            let shard = shard.read().unwrap();
+            selection.extend(shard.iter()
+                .filter(|&(path, _)| {
+                    let path: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+                    matches(&pattern, &path)
+                })
+                .map(|(_, actor)| actor.clone()));
+        }
+        selection.retain(|actor| actor.is_alive());
+        selection.sort_by(|a, b| a.path().logical_path().cmp(b.path().logical_path()));
+        selection
+    }
+}
+
+/// Tells whether a path matches a pattern, both given as their segments.
+///
+/// A `**` segment matches any number of segments (including none). Anonymous actors (whose name
+/// starts with a `$`) are only matched by segments starting with a `$`, so that `/user/*` does not
+/// give the Futures of the user.
+fn matches(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", pattern)) => (0..path.len() + 1).any(|skipped| matches(pattern, &path[skipped..])),
+        Some((segment, pattern)) => match path.split_first() {
+            Some((name, path)) => {
+                (segment.starts_with('$') || !name.starts_with('$')) &&
+                matches_segment(segment, name) && matches(pattern, path)
+            },
+            None => false,
+        },
+    }
+}
+
+/// Tells whether a name matches a segment of a pattern, where a `*` matches any characters.
+fn matches_segment(pattern: &str, name: &str) -> bool {
+    match pattern.find('*') {
+        None => pattern == name,
+        Some(star) => {
+            let (prefix, pattern) = (&pattern[..star], &pattern[star + 1..]);
+            if !name.starts_with(prefix) {
+                return false;
+            }
+            let name = &name[prefix.len()..];
+            name.char_indices()
+                .map(|(index, _)| index)
+                .chain(Some(name.len()))
+                .any(|index| matches_segment(pattern, &name[index..]))
+        },
+    }
+}
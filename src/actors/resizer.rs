@@ -0,0 +1,73 @@
+use actors::ActorRef;
+
+/// Settings to grow or shrink the pool of a Router with the pressure on its routees.
+///
+/// A routee is busy when at least `pressure_threshold` messages are waiting in its mailbox. Every
+/// `messages_per_resize` messages the Router looks at its routees:
+///
+///   * If all of them are busy, `rampup_rate` times the size of the pool is added to it.
+///   * If less than `backoff_threshold` of them are busy, `backoff_rate` times the size of the pool
+///     is removed from it, the routees with the fewest waiting messages being stopped.
+///
+/// The size of the pool always stays between `lower_bound` and `upper_bound`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Resizer {
+    /// Minimal number of routees.
+    pub lower_bound: usize,
+    /// Maximal number of routees.
+    pub upper_bound: usize,
+    /// Number of messages waiting in the mailbox of a routee from which it is busy.
+    pub pressure_threshold: usize,
+    /// Part of the pool added when all the routees are busy.
+    pub rampup_rate: f64,
+    /// Ratio of busy routees below which the pool shrinks.
+    pub backoff_threshold: f64,
+    /// Part of the pool removed when it shrinks.
+    pub backoff_rate: f64,
+    /// Number of messages routed between two checks of the pressure.
+    pub messages_per_resize: usize,
+}
+
+impl Resizer {
+    /// Creates a Resizer keeping the pool between the given bounds, with the default settings for
+    /// the rest.
+    ///
+    /// This panics if `lower_bound` is greater than `upper_bound` or if `upper_bound` is 0.
+    pub fn new(lower_bound: usize, upper_bound: usize) -> Resizer {
+        if lower_bound > upper_bound || upper_bound == 0 {
+            panic!("Invalid bounds for a Resizer: {} to {}", lower_bound, upper_bound);
+        }
+        Resizer {
+            lower_bound: lower_bound,
+            upper_bound: upper_bound,
+            pressure_threshold: 1,
+            rampup_rate: 0.2,
+            backoff_threshold: 0.3,
+            backoff_rate: 0.1,
+            messages_per_resize: 10,
+        }
+    }
+
+    /// Gives the number of routees to add to the pool, or to remove from it if negative.
+    pub fn capacity(&self, routees: &[ActorRef]) -> i32 {
+        let size = routees.len();
+        let busy = routees.iter().filter(|routee| routee.mailbox_len() >= self.pressure_threshold).count();
+        let delta = if size == 0 {
+            0
+        } else if busy == size {
+            (self.rampup_rate * size as f64).ceil() as i32
+        } else if (busy as f64 / size as f64) < self.backoff_threshold {
+            -(self.backoff_rate * size as f64).ceil() as i32
+        } else {
+            0
+        };
+        let proposed = size as i32 + delta;
+        if proposed < self.lower_bound as i32 {
+            self.lower_bound as i32 - size as i32
+        } else if proposed > self.upper_bound as i32 {
+            self.upper_bound as i32 - size as i32
+        } else {
+            delta
+        }
+    }
+}
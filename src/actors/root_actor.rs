@@ -13,7 +13,7 @@ use std::any::Any;
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::Sender;
 
-use actors::{Actor, ActorCell, ActorContext, ActorRef};
+use actors::{Actor, ActorCell, ActorContext, ActorRef, RobotsError};
 use actors::props::ActorFactory;
 
 pub struct RootActor;
@@ -26,10 +26,13 @@ impl RootActor {
 
 impl Actor for RootActor {
     fn receive(&self, message: Box<Any>, context: ActorCell) {
-        if let Ok(message) = Box::<Any>::downcast::<(Arc<ActorFactory>, String, Arc<Mutex<Sender<Result<ActorRef, &'static str>>>>)>(message) {
+        if let Ok(message) = Box::<Any>::downcast::<(Arc<ActorFactory>, Option<String>, Arc<Mutex<Sender<Result<ActorRef, RobotsError>>>>)>(message) {
             let tmp = *message;
             let (props, name, tx) = tmp;
-            let actor_ref = context.actor_of(props, name);
+            let actor_ref = match name {
+                Some(name) => context.actor_of(props, name),
+                None => context.try_actor_of_anonymous(props),
+            };
             // FIXME(gamazeps): error handling.
             let _res = tx.lock().unwrap().send(actor_ref);
         }
@@ -0,0 +1,335 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rand::{self, Rng};
+
+use actors::{Actor, ActorCell, ActorContext, ActorRef, InnerMessage, Message, Props, Resizer};
+use actors::props::ActorFactory;
+
+/// The ways a Router can choose the routees a message is sent to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RoutingStrategy {
+    /// Each routee gets a message in turn.
+    RoundRobin,
+    /// The routee is chosen at random.
+    Random,
+    /// The routee with the fewest messages waiting in its mailbox is chosen.
+    SmallestMailbox,
+    /// Every routee gets every message.
+    Broadcast,
+}
+
+/// How a Router chooses its routees.
+///
+/// Consistent hashing needs the key of the messages, so it is not one of the public strategies:
+/// such Routers are created with `Router::consistent_hashing`, and messages with the same key
+/// reach the same routee.
+#[derive(Clone)]
+enum Routing<M> {
+    Strategy(RoutingStrategy),
+    ConsistentHashing(HashKey<M>),
+}
+
+/// Closure giving the hash of the key of a message, used by consistent hashing Routers.
+pub type HashKey<M> = Arc<Fn(&M) -> u64 + Send + Sync>;
+
+/// Number of points of each routee on the hash ring, the more there are the more evenly the keys
+/// are spread.
+const VIRTUAL_NODES: usize = 32;
+
+fn hash<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn hash_key<M, K, F>(key: F) -> HashKey<M>
+    where K: Hash,
+          F: Fn(&M) -> K + Send + Sync + 'static
+{
+    Arc::new(move |message| hash(&key(message)))
+}
+
+/// Messages understood by a Router, in addition to the messages it routes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RouterMessage {
+    /// Adds routees to the pool if positive, or stops some of them if negative, starting with the
+    /// routees with the fewest waiting messages.
+    ///
+    /// This is ignored by group Routers.
+    AdjustPoolSize(i32),
+    /// Completes the sending Future with a Vec<ActorRef> of the current routees.
+    GetRoutees,
+}
+
+/// Wraps a message so that it is sent to all the routees whatever the strategy of the Router.
+#[derive(Clone)]
+pub struct Broadcast<M: Message>(pub M);
+
+/// Where the routees of a Router come from.
+#[derive(Clone)]
+enum Routees {
+    /// A pool of children of the Router, created with the given Props.
+    Pool(usize, Arc<ActorFactory>),
+    /// A group of existing actors, with the given paths.
+    Group(Vec<String>),
+}
+
+/// Actor sending the messages of type `M` it receives to its routees.
+///
+/// The routees are either a pool of children of the Router, or a group of existing actors given
+/// by their paths.
+///
+/// The routees get the message with its original sender, so they can answer directly. Those of a
+/// pool are supervised by the Router as any other child: they are restarted when they fail, and
+/// those that stopped are removed from the pool. A pool can grow and shrink with the pressure on
+/// its routees, see `Resizer`. Those of a group are removed when they terminate, and come back if
+/// an actor is created again with their path.
+///
+/// Messages of other types than `M`, `Broadcast<M>` and `RouterMessage` are dropped.
+pub struct Router<M: Message> {
+    routing: Routing<M>,
+    source: Routees,
+    resizer: Option<Resizer>,
+    routees: Mutex<Vec<ActorRef>>,
+    next: AtomicUsize,
+    // Number of messages routed, to know when to resize the pool.
+    routed: AtomicUsize,
+    // Hash ring of the routees for consistent hashing, built when needed.
+    ring: Mutex<Option<BTreeMap<u64, ActorRef>>>,
+    _message: PhantomData<M>,
+}
+
+impl<M: Message> Router<M> {
+    /// Gives Props for a Router with a pool of `size` routees created with the `routee_props`.
+    pub fn pool(strategy: RoutingStrategy, size: usize, routee_props: Arc<ActorFactory>) -> Arc<ActorFactory> {
+        Props::new(Arc::new(Router::<M>::new), (Routing::Strategy(strategy), Routees::Pool(size, routee_props), None))
+    }
+
+    /// Gives Props for a Router with a pool of routees created with the `routee_props`, whose size
+    /// is adapted by the `resizer`.
+    ///
+    /// The pool starts with `resizer.lower_bound` routees.
+    pub fn resizable_pool(strategy: RoutingStrategy, resizer: Resizer, routee_props: Arc<ActorFactory>) -> Arc<ActorFactory> {
+        Props::new(Arc::new(Router::<M>::new),
+                   (Routing::Strategy(strategy), Routees::Pool(resizer.lower_bound, routee_props), Some(resizer)))
+    }
+
+    /// Gives Props for a Router sending to the actors with the given paths.
+    ///
+    /// The paths are resolved from the path of the Router, so they can be relative to it, and
+    /// only the actors that exist get messages.
+    pub fn group(strategy: RoutingStrategy, paths: Vec<String>) -> Arc<ActorFactory> {
+        Props::new(Arc::new(Router::<M>::new), (Routing::Strategy(strategy), Routees::Group(paths), None))
+    }
+
+    /// Gives Props for a Router with a pool of `size` routees created with the `routee_props`,
+    /// sending the messages with the same `key` to the same routee.
+    ///
+    /// The routees are placed on a hash ring, so when routees are added or removed only the keys
+    /// of the ring around them move to other routees.
+    pub fn consistent_hashing<K, F>(size: usize, routee_props: Arc<ActorFactory>, key: F) -> Arc<ActorFactory>
+        where K: Hash,
+              F: Fn(&M) -> K + Send + Sync + 'static
+    {
+        Props::new(Arc::new(Router::<M>::new),
+                   (Routing::ConsistentHashing(hash_key(key)), Routees::Pool(size, routee_props), None))
+    }
+
+    /// Gives Props for a Router sending to the actors with the given paths, the messages with the
+    /// same `key` going to the same actor as long as the existing actors do not change.
+    pub fn consistent_hashing_group<K, F>(paths: Vec<String>, key: F) -> Arc<ActorFactory>
+        where K: Hash,
+              F: Fn(&M) -> K + Send + Sync + 'static
+    {
+        Props::new(Arc::new(Router::<M>::new),
+                   (Routing::ConsistentHashing(hash_key(key)), Routees::Group(paths), None))
+    }
+
+    fn new((routing, source, resizer): (Routing<M>, Routees, Option<Resizer>)) -> Router<M> {
+        Router {
+            routing: routing,
+            source: source,
+            resizer: resizer,
+            routees: Mutex::new(Vec::new()),
+            next: AtomicUsize::new(0),
+            routed: AtomicUsize::new(0),
+            ring: Mutex::new(None),
+            _message: PhantomData,
+        }
+    }
+
+    /// Gives the routees that are still alive, and forgets the others.
+    ///
+    /// For a group, the paths without a routee are resolved again.
+    fn routees(&self, context: &ActorCell) -> Vec<ActorRef> {
+        let mut routees = self.routees.lock().unwrap();
+        let len = routees.len();
+        routees.retain(|routee| routee.is_alive());
+        let mut changed = routees.len() != len;
+        if let Routees::Group(ref paths) = self.source {
+            if routees.len() < paths.len() {
+                let router_path = context.path();
+                let resolved: Vec<ActorRef> = paths.iter().filter_map(|path| {
+                    let path = router_path.resolve(path);
+                    routees.iter().find(|routee| routee.path().logical_path() == &path).cloned()
+                        .or_else(|| context.lookup(&path))
+                }).collect();
+                changed |= resolved.len() != routees.len();
+                *routees = resolved;
+            }
+        }
+        if changed {
+            *self.ring.lock().unwrap() = None;
+        }
+        routees.clone()
+    }
+
+    fn add_routees(&self, n: usize, context: &ActorCell) {
+        let props = match self.source {
+            Routees::Pool(_, ref props) => props,
+            Routees::Group(_) => return warn!("{} is a group Router, it can't be resized", context.path().logical_path()),
+        };
+        let mut routees = self.routees.lock().unwrap();
+        for _ in 0..n {
+            routees.push(context.actor_of_anonymous(props.clone()));
+        }
+        *self.ring.lock().unwrap() = None;
+    }
+
+    fn remove_routees(&self, n: usize, context: &ActorCell) {
+        if let Routees::Group(_) = self.source {
+            return warn!("{} is a group Router, it can't be resized", context.path().logical_path());
+        }
+        let mut routees = self.routees.lock().unwrap();
+        // The routees with the fewest waiting messages are stopped first.
+        let mut by_load: Vec<(usize, usize)> = routees.iter().map(|routee| routee.mailbox_len()).enumerate().collect();
+        by_load.sort_by_key(|&(_, len)| len);
+        let stopped: Vec<usize> = by_load.into_iter().take(n).map(|(i, _)| i).collect();
+        let mut i = 0;
+        routees.retain(|routee| {
+            let kept = !stopped.contains(&i);
+            if !kept {
+                context.stop(routee.clone());
+            }
+            i += 1;
+            kept
+        });
+        *self.ring.lock().unwrap() = None;
+    }
+
+    /// Grows or shrinks the pool if it has a Resizer and enough messages were routed since the
+    /// last check.
+    fn resize(&self, context: &ActorCell) {
+        let resizer = match self.resizer {
+            Some(ref resizer) => resizer,
+            None => return,
+        };
+        if self.routed.fetch_add(1, Ordering::Relaxed) % resizer.messages_per_resize.max(1) != 0 {
+            return;
+        }
+        let delta = resizer.capacity(&self.routees(context));
+        if delta > 0 {
+            self.add_routees(delta as usize, context);
+        } else if delta < 0 {
+            self.remove_routees((-delta) as usize, context);
+        }
+    }
+
+    /// Gives the routee owning the given hash on the ring, which is built if needed.
+    fn ring_routee(&self, hash_value: u64, routees: &[ActorRef]) -> ActorRef {
+        let mut ring = self.ring.lock().unwrap();
+        let ring = ring.get_or_insert_with(|| {
+            let mut ring = BTreeMap::new();
+            for routee in routees.iter() {
+                for node in 0..VIRTUAL_NODES {
+                    ring.insert(hash(&(routee.path().logical_path(), node)), routee.clone());
+                }
+            }
+            ring
+        });
+        // The routee owning a hash is the first one after it on the ring.
+        ring.range(hash_value..).next().or_else(|| ring.iter().next()).unwrap().1.clone()
+    }
+
+    /// Sends the message to a routee, keeping its original sender.
+    fn send(&self, routee: &ActorRef, message: M, context: &ActorCell) {
+        let _ = routee.receive(InnerMessage::Message(Box::new(message)), context.sender());
+    }
+
+    fn route(&self, message: M, context: &ActorCell) {
+        self.resize(context);
+        let routees = self.routees(context);
+        if routees.is_empty() {
+            warn!("{} has no routee to send a message to", context.path().logical_path());
+            return;
+        }
+        let strategy = match self.routing {
+            Routing::Strategy(strategy) => strategy,
+            Routing::ConsistentHashing(ref hash_key) => {
+                let routee = self.ring_routee(hash_key(&message), &routees);
+                return self.send(&routee, message, context);
+            },
+        };
+        let routee = match strategy {
+            RoutingStrategy::RoundRobin => &routees[self.next.fetch_add(1, Ordering::Relaxed) % routees.len()],
+            RoutingStrategy::Random => &routees[rand::thread_rng().gen_range(0, routees.len())],
+            RoutingStrategy::SmallestMailbox => routees.iter().min_by_key(|routee| routee.mailbox_len()).unwrap(),
+            RoutingStrategy::Broadcast => {
+                self.broadcast(routees, message, context);
+                return;
+            },
+        };
+        self.send(routee, message, context);
+    }
+
+    fn broadcast(&self, routees: Vec<ActorRef>, message: M, context: &ActorCell) {
+        for routee in routees.iter() {
+            self.send(routee, message.clone(), context);
+        }
+    }
+}
+
+impl<M: Message> Actor for Router<M> {
+    fn pre_start(&self, context: ActorCell) {
+        let size = match self.source {
+            Routees::Pool(size, _) => size,
+            // The routees of a group are resolved when they are needed.
+            Routees::Group(_) => return,
+        };
+        // When the Router is restarted its routees are still there, they are kept in the pool.
+        let existing = {
+            let mut routees = self.routees.lock().unwrap();
+            *routees = context.children().values().cloned().collect();
+            // The children are in no particular order, this keeps the routing predictable.
+            routees.sort_by(|a, b| a.path().logical_path().cmp(b.path().logical_path()));
+            routees.len()
+        };
+        *self.ring.lock().unwrap() = None;
+        self.add_routees(size.saturating_sub(existing), &context);
+    }
+
+    fn receive(&self, message: Box<Any>, context: ActorCell) {
+        let message = match Box::<Any>::downcast::<M>(message) {
+            Ok(message) => return self.route(*message, &context),
+            Err(message) => message,
+        };
+        let message = match Box::<Any>::downcast::<Broadcast<M>>(message) {
+            Ok(message) => return self.broadcast(self.routees(&context), message.0, &context),
+            Err(message) => message,
+        };
+        match Box::<Any>::downcast::<RouterMessage>(message) {
+            Ok(message) => match *message {
+                RouterMessage::AdjustPoolSize(n) if n >= 0 => self.add_routees(n as usize, &context),
+                RouterMessage::AdjustPoolSize(n) => self.remove_routees(n.unsigned_abs() as usize, &context),
+                RouterMessage::GetRoutees => context.complete(context.sender(), self.routees(&context)),
+            },
+            Err(_) => warn!("{} received a message of an unexpected type", context.path().logical_path()),
+        }
+    }
+}
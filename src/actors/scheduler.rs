@@ -0,0 +1,97 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A task waiting for its deadline in the scheduler.
+struct ScheduledTask {
+    deadline: Instant,
+    task: Box<Fn() + Send>,
+}
+
+// The ordering is reversed on the deadline so that the BinaryHeap (which is a max-heap) gives us
+// the task with the closest deadline first.
+impl Ord for ScheduledTask {
+    fn cmp(&self, other: &ScheduledTask) -> Ordering {
+        other.deadline.cmp(&self.deadline)
+    }
+}
+
+impl PartialOrd for ScheduledTask {
+    fn partial_cmp(&self, other: &ScheduledTask) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ScheduledTask {
+    fn eq(&self, other: &ScheduledTask) -> bool {
+        self.deadline == other.deadline
+    }
+}
+
+impl Eq for ScheduledTask {}
+
+enum SchedulerMessage {
+    Schedule(ScheduledTask),
+    Stop,
+}
+
+/// The scheduler runs tasks after a given delay.
+///
+/// It owns a single thread that sleeps until the closest deadline, so scheduling a task does not
+/// cost a thread. The tasks are run on the scheduler thread and are thus expected to be short,
+/// sending a message to an actor is the intended use.
+pub struct Scheduler {
+    sender: Mutex<Sender<SchedulerMessage>>,
+}
+
+impl Scheduler {
+    /// Creates a new Scheduler and starts its thread.
+    pub fn new() -> Scheduler {
+        let (tx, rx) = channel();
+        let _ = thread::spawn(move || Scheduler::run(rx));
+        Scheduler { sender: Mutex::new(tx) }
+    }
+
+    /// Runs the task once the delay has passed.
+    pub fn schedule_once(&self, delay: Duration, task: Box<Fn() + Send>) {
+        let task = ScheduledTask {
+            deadline: Instant::now() + delay,
+            task: task,
+        };
+        // If the scheduler was stopped the task is simply dropped.
+        let _ = self.sender.lock().unwrap().send(SchedulerMessage::Schedule(task));
+    }
+
+    /// Stops the scheduler thread, the tasks that were not run yet are dropped.
+    pub fn stop(&self) {
+        let _ = self.sender.lock().unwrap().send(SchedulerMessage::Stop);
+    }
+
+    fn run(rx: Receiver<SchedulerMessage>) {
+        let mut tasks = BinaryHeap::new();
+        loop {
+            let now = Instant::now();
+            while tasks.peek().map_or(false, |task: &ScheduledTask| task.deadline <= now) {
+                let task = tasks.pop().unwrap();
+                // A panicking task must not stop the thread, the other tasks would never be run.
+                if panic::catch_unwind(AssertUnwindSafe(|| (task.task)())).is_err() {
+                    error!("A scheduled task panicked");
+                }
+            }
+
+            let message = match tasks.peek() {
+                Some(task) => rx.recv_timeout(task.deadline - now),
+                None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match message {
+                Ok(SchedulerMessage::Schedule(task)) => tasks.push(task),
+                Ok(SchedulerMessage::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {},
+            }
+        }
+    }
+}
@@ -0,0 +1,514 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use rand::{self, Rng};
+
+use actors::{Actor, ActorCell, ActorContext, ActorRef, ActorSystem, Props, RobotsError};
+use cluster::failure_detector::PhiAccrualFailureDetector;
+use cluster::gossip::Gossip;
+use cluster::member::{ClusterEvent, Member, MemberDowned, MemberExited, MemberJoined, MemberLeft, MemberRemoved,
+                      MemberStatus, MemberUp, ReachableMember, UnreachableMember};
+use cluster::protocol::{ClusterMessage, RemotePayload};
+use cluster::transport::Transport;
+
+/// Settings of a node of the cluster.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClusterSettings {
+    /// Address and port the node listens on, such as `127.0.0.1:2552`. With the port 0 a free
+    /// port is picked.
+    pub address: String,
+    /// Interval between two rounds of gossip, where the node sends its membership state to another
+    /// member.
+    pub gossip_interval: Duration,
+    /// Interval between two heartbeats sent to each member.
+    pub heartbeat_interval: Duration,
+    /// Suspicion level (phi) of the failure detector above which a member is unreachable.
+    ///
+    /// Lower values detect failures faster, but make mistakes more likely.
+    pub failure_detector_threshold: f64,
+    /// Minimum standard deviation of the intervals between heartbeats used by the failure
+    /// detector, so that very regular heartbeats do not make it too sensitive.
+    pub min_std_deviation: Duration,
+    /// Pause in the heartbeats that is tolerated on top of their usual interval.
+    pub acceptable_heartbeat_pause: Duration,
+    /// Time after which the leader declares an unreachable member down, never if `None`.
+    pub auto_down_unreachable_after: Option<Duration>,
+}
+
+impl ClusterSettings {
+    /// Creates the settings of a node listening on the given address, with the default intervals.
+    pub fn new(address: String) -> ClusterSettings {
+        ClusterSettings {
+            address: address,
+            gossip_interval: Duration::from_secs(1),
+            heartbeat_interval: Duration::from_secs(1),
+            failure_detector_threshold: 8.0,
+            min_std_deviation: Duration::from_millis(100),
+            acceptable_heartbeat_pause: Duration::from_secs(3),
+            auto_down_unreachable_after: None,
+        }
+    }
+}
+
+/// Membership of an actor system in a cluster of actor systems, possibly on other hosts.
+///
+/// A node joins the cluster through seed nodes, the first seed node starts the cluster when it
+/// joins itself. The membership state is then spread by gossip: each node regularly sends it to a
+/// random member. The leader (the reachable member with the lowest address) moves the members from
+/// joining to up, and removes the members that left or were downed.
+///
+/// The nodes send heartbeats to each other, and monitor them with a phi accrual failure detector:
+/// members that stop sending heartbeats become unreachable. Actors monitoring distant actors of an
+/// unreachable member are notified with `RobotsError::Unreachable`, and with
+/// `RobotsError::ActorStopped` when the member is removed.
+///
+/// The changes of the membership are published on the EventStream of the actor system, classified
+/// as `ClusterEvent`.
+#[derive(Clone)]
+pub struct Cluster {
+    inner: Arc<InnerCluster>,
+}
+
+impl Cluster {
+    /// Makes the actor system a node of a cluster, listening on the address of the settings.
+    ///
+    /// The node is not a member until it joins a cluster with `join`. This fails if the address
+    /// can't be listened on, or if the actor system already is a node.
+    pub fn new(actor_system: &ActorSystem, settings: ClusterSettings) -> Result<Cluster, RobotsError> {
+        let daemon: Arc<RwLock<Option<ActorRef>>> = Arc::new(RwLock::new(None));
+        let handler_daemon = daemon.clone();
+        let handler_system = actor_system.clone();
+        let transport = Transport::bind(&settings.address, Arc::new(move |message| {
+            if let Some(daemon) = handler_daemon.read().unwrap().clone() {
+                handler_system.tell(daemon, DaemonMessage::Received(message));
+            }
+        }))?;
+        let self_member = Member::new(transport.address().to_owned(), rand::random());
+        let cluster = Cluster {
+            inner: Arc::new(InnerCluster {
+                actor_system: actor_system.clone(),
+                settings: settings,
+                self_member: self_member,
+                transport: transport,
+                state: Mutex::new(ClusterState {
+                    gossip: Gossip::new(),
+                    seed_nodes: Vec::new(),
+                    failure_detectors: HashMap::new(),
+                    unreachable: HashMap::new(),
+                    stopped: false,
+                }),
+                daemon: daemon.clone(),
+            }),
+        };
+        let event_stream = actor_system.event_stream();
+        let cluster_event = TypeId::of::<ClusterEvent>();
+        event_stream.add_class(TypeId::of::<MemberJoined>(), cluster_event);
+        event_stream.add_class(TypeId::of::<MemberUp>(), cluster_event);
+        event_stream.add_class(TypeId::of::<MemberLeft>(), cluster_event);
+        event_stream.add_class(TypeId::of::<MemberExited>(), cluster_event);
+        event_stream.add_class(TypeId::of::<MemberDowned>(), cluster_event);
+        event_stream.add_class(TypeId::of::<MemberRemoved>(), cluster_event);
+        event_stream.add_class(TypeId::of::<UnreachableMember>(), cluster_event);
+        event_stream.add_class(TypeId::of::<ReachableMember>(), cluster_event);
+        let props = Props::new(Arc::new(ClusterDaemon::new), cluster.clone());
+        *daemon.write().unwrap() = Some(actor_system.system_actor_of(props, "cluster".to_owned())?);
+        Ok(cluster)
+    }
+
+    /// Address and port of the node.
+    pub fn address(&self) -> &str {
+        &self.inner.self_member.address
+    }
+
+    /// Joins the cluster of the given seed nodes (given by their address and port).
+    ///
+    /// The node asks all the seed nodes to join until one of them answers. If the first seed node
+    /// is this node, it starts a new cluster.
+    pub fn join(&self, seed_nodes: Vec<String>) {
+        self.inner.tell_daemon(DaemonMessage::Join(seed_nodes));
+    }
+
+    /// Leaves the cluster gracefully.
+    ///
+    /// The node is removed once the leader saw that it is leaving, it then stops taking part in
+    /// the cluster.
+    pub fn leave(&self) {
+        self.inner.tell_daemon(DaemonMessage::Leave);
+    }
+
+    /// Declares the member with the given address down, it is then removed by the leader.
+    pub fn down(&self, address: &str) {
+        self.inner.tell_daemon(DaemonMessage::Down(address.to_owned()));
+    }
+
+    /// Members of the cluster known by this node, which were not removed, by address.
+    pub fn members(&self) -> Vec<Member> {
+        self.inner.state.lock().unwrap().gossip.members()
+    }
+
+    /// This node as a member of the cluster, it is joining while it is not part of the
+    /// membership.
+    pub fn self_member(&self) -> Member {
+        let state = self.inner.state.lock().unwrap();
+        state.gossip.member(self.address()).cloned().unwrap_or_else(|| self.inner.self_member.clone())
+    }
+
+    /// Address of the leader of the cluster, as seen by this node.
+    pub fn leader(&self) -> Option<String> {
+        self.inner.leader(&self.inner.state.lock().unwrap())
+    }
+
+    /// Members that this node can't reach.
+    pub fn unreachable(&self) -> Vec<Member> {
+        let state = self.inner.state.lock().unwrap();
+        state.gossip.members().into_iter().filter(|member| state.unreachable.contains_key(&member.address)).collect()
+    }
+
+    /// Actor system of the node.
+    pub(crate) fn actor_system(&self) -> &ActorSystem {
+        &self.inner.actor_system
+    }
+
+    /// Up members, which take part in the work of the cluster (such as hosting shards), by
+    /// address.
+    pub(crate) fn up_members(&self) -> Vec<Member> {
+        self.members().into_iter().filter(|member| member.status == MemberStatus::Up).collect()
+    }
+
+    /// Sends the serialized message to the actor with the given logical path on the node with the
+    /// given address, which gets it as a `RemotePayload`.
+    pub(crate) fn deliver(&self, address: &str, path: &str, message: Vec<u8>) {
+        self.inner.transport.send(address, &ClusterMessage::Deliver(path.to_owned(), message));
+    }
+
+    /// Stops taking part in the cluster without leaving it, as if the node had crashed.
+    pub fn shutdown(&self) {
+        self.inner.tell_daemon(DaemonMessage::Shutdown);
+        self.inner.state.lock().unwrap().stopped = true;
+        self.inner.transport.shutdown();
+    }
+}
+
+struct InnerCluster {
+    actor_system: ActorSystem,
+    settings: ClusterSettings,
+    self_member: Member,
+    transport: Transport,
+    state: Mutex<ClusterState>,
+    daemon: Arc<RwLock<Option<ActorRef>>>,
+}
+
+struct ClusterState {
+    gossip: Gossip,
+    // Seed nodes the node is joining through, empty once it is part of the membership.
+    seed_nodes: Vec<String>,
+    // Heartbeats of each member.
+    failure_detectors: HashMap<String, PhiAccrualFailureDetector>,
+    // Members which can't be reached, with the time they were detected.
+    unreachable: HashMap<String, Instant>,
+    stopped: bool,
+}
+
+impl InnerCluster {
+    fn tell_daemon(&self, message: DaemonMessage) {
+        if let Some(daemon) = self.daemon.read().unwrap().clone() {
+            self.actor_system.tell(daemon, message);
+        }
+    }
+
+    fn address(&self) -> &str {
+        &self.self_member.address
+    }
+
+    /// Whether this node is part of the membership.
+    fn is_member(&self, state: &ClusterState) -> bool {
+        state.gossip.member(self.address()).map_or(false, |member| member.uid == self.self_member.uid)
+    }
+
+    /// Reachable member with the lowest address among the up (or leaving) members, or among the
+    /// joining ones if none is up.
+    fn leader(&self, state: &ClusterState) -> Option<String> {
+        let candidates: Vec<Member> = state.gossip.members().into_iter()
+            .filter(|member| !state.unreachable.contains_key(&member.address))
+            .collect();
+        candidates.iter().find(|member| member.status == MemberStatus::Up || member.status == MemberStatus::Leaving)
+                  .or_else(|| candidates.iter().find(|member| member.status == MemberStatus::Joining))
+                  .map(|member| member.address.clone())
+    }
+
+    fn failure_detector(&self) -> PhiAccrualFailureDetector {
+        PhiAccrualFailureDetector::new(self.settings.failure_detector_threshold,
+                                       self.settings.heartbeat_interval,
+                                       self.settings.min_std_deviation,
+                                       self.settings.acceptable_heartbeat_pause)
+    }
+
+    /// Members the node sends its gossip and heartbeats to.
+    fn peers(&self, state: &ClusterState) -> Vec<Member> {
+        state.gossip.members().into_iter().filter(|member| member.address != self.address()).collect()
+    }
+
+    /// Applies the change to the membership, and publishes the events for the members it changed.
+    fn change<F: FnOnce(&mut Gossip) -> bool>(&self, state: &mut ClusterState, change: F) {
+        let before = state.gossip.clone();
+        if !change(&mut state.gossip) {
+            return;
+        }
+        let event_stream = self.actor_system.event_stream();
+        for member in state.gossip.all_members() {
+            if before.member(&member.address) == Some(member) {
+                continue;
+            }
+            let member = member.clone();
+            match member.status {
+                MemberStatus::Joining => event_stream.publish(MemberJoined { member: member }),
+                MemberStatus::Up => event_stream.publish(MemberUp { member: member }),
+                MemberStatus::Leaving => event_stream.publish(MemberLeft { member: member }),
+                MemberStatus::Exiting => event_stream.publish(MemberExited { member: member }),
+                MemberStatus::Down => event_stream.publish(MemberDowned { member: member }),
+                MemberStatus::Removed => {
+                    let _ = state.failure_detectors.remove(&member.address);
+                    let _ = state.unreachable.remove(&member.address);
+                    self.actor_system.distant_node_removed(&member.address);
+                    event_stream.publish(MemberRemoved { member: member });
+                },
+            }
+        }
+    }
+
+    fn handle(&self, message: DaemonMessage, context: &ActorCell) {
+        let mut state = self.state.lock().unwrap();
+        if state.stopped {
+            if let DaemonMessage::Shutdown = message {
+                context.kill_me();
+            }
+            return;
+        }
+        match message {
+            DaemonMessage::Join(seed_nodes) => {
+                state.seed_nodes = seed_nodes;
+                self.join(&mut state);
+            },
+            DaemonMessage::Leave => {
+                let mut member = match state.gossip.member(self.address()) {
+                    Some(member) => member.clone(),
+                    None => return,
+                };
+                member.status = MemberStatus::Leaving;
+                self.change(&mut state, |gossip| gossip.update(member));
+            },
+            DaemonMessage::Down(address) => {
+                let mut member = match state.gossip.member(&address) {
+                    Some(member) => member.clone(),
+                    None => return,
+                };
+                member.status = MemberStatus::Down;
+                self.change(&mut state, |gossip| gossip.update(member));
+            },
+            DaemonMessage::Received(ClusterMessage::Join(member)) => {
+                if !self.is_member(&state) {
+                    return;
+                }
+                let incarnation = state.gossip.member(&member.address).cloned();
+                match incarnation {
+                    Some(ref known) if known.uid != member.uid && known.status != MemberStatus::Removed => {
+                        info!("{} tries to join again, its previous incarnation must be removed first",
+                              member.address);
+                        return;
+                    },
+                    _ => {},
+                }
+                let address = member.address.clone();
+                self.change(&mut state, |gossip| gossip.update(member));
+                self.transport.send(&address, &ClusterMessage::Gossip(state.gossip.clone()));
+            },
+            DaemonMessage::Received(ClusterMessage::Gossip(gossip)) => {
+                self.change(&mut state, |known| known.merge(&gossip));
+                if self.is_member(&state) {
+                    state.seed_nodes.clear();
+                }
+            },
+            DaemonMessage::Received(ClusterMessage::Deliver(path, message)) => {
+                match self.actor_system.lookup(&path) {
+                    Some(actor) => {
+                        let _ = self.actor_system.tell(actor, RemotePayload(message));
+                    },
+                    None => warn!("Received a message for {} which does not exist", path),
+                }
+            },
+            DaemonMessage::Received(ClusterMessage::Heartbeat(address)) => {
+                state.failure_detectors.entry(address).or_insert_with(|| self.failure_detector()).heartbeat();
+            },
+            DaemonMessage::GossipTick => {
+                self.join(&mut state);
+                self.leader_actions(&mut state);
+                self.gossip(&mut state);
+                self.schedule(context, self.settings.gossip_interval, DaemonMessage::GossipTick);
+            },
+            DaemonMessage::HeartbeatTick => {
+                self.heartbeat(&mut state);
+                self.schedule(context, self.settings.heartbeat_interval, DaemonMessage::HeartbeatTick);
+            },
+            DaemonMessage::Shutdown => {},
+        }
+    }
+
+    fn schedule(&self, context: &ActorCell, delay: Duration, message: DaemonMessage) {
+        self.actor_system.schedule_once(delay, context.actor_ref(), message);
+    }
+
+    /// Asks the seed nodes to join, or starts the cluster if this node is the first seed node.
+    fn join(&self, state: &mut ClusterState) {
+        if state.seed_nodes.is_empty() || self.is_member(state) {
+            return;
+        }
+        if state.seed_nodes[0] == self.address() {
+            let mut member = self.self_member.clone();
+            member.status = MemberStatus::Up;
+            member.up_number = state.gossip.next_up_number();
+            self.change(state, |gossip| gossip.update(member));
+            state.seed_nodes.clear();
+            return;
+        }
+        for seed_node in state.seed_nodes.iter().filter(|seed_node| *seed_node != self.address()) {
+            self.transport.send(seed_node, &ClusterMessage::Join(self.self_member.clone()));
+        }
+    }
+
+    /// Moves the members to their next status if this node is the leader.
+    fn leader_actions(&self, state: &mut ClusterState) {
+        if self.leader(state).as_ref().map(|leader| &leader[..]) != Some(self.address()) {
+            return;
+        }
+        if let Some(auto_down) = self.settings.auto_down_unreachable_after {
+            let downed: Vec<String> = state.unreachable.iter()
+                .filter(|&(_, since)| since.elapsed() >= auto_down)
+                .map(|(address, _)| address.clone())
+                .collect();
+            for address in downed {
+                if let Some(mut member) = state.gossip.member(&address).cloned() {
+                    info!("Downing {} which has been unreachable for too long", address);
+                    member.status = MemberStatus::Down;
+                    self.change(state, |gossip| gossip.update(member));
+                }
+            }
+        }
+        let mut removed = Vec::new();
+        for mut member in state.gossip.members() {
+            match member.status {
+                MemberStatus::Joining => {
+                    member.status = MemberStatus::Up;
+                    member.up_number = state.gossip.next_up_number();
+                },
+                MemberStatus::Leaving => member.status = MemberStatus::Exiting,
+                MemberStatus::Exiting | MemberStatus::Down => {
+                    member.status = MemberStatus::Removed;
+                    removed.push(member.address.clone());
+                },
+                MemberStatus::Up | MemberStatus::Removed => continue,
+            }
+            self.change(state, |gossip| gossip.update(member));
+        }
+        // The removed nodes are told directly, as the others no longer gossip with them.
+        for address in removed.iter().filter(|address| *address != self.address()) {
+            self.transport.send(address, &ClusterMessage::Gossip(state.gossip.clone()));
+            self.transport.disconnect(address);
+        }
+    }
+
+    /// Sends the membership to a random member, or to all of them if this node was removed, as it
+    /// then stops taking part in the cluster.
+    fn gossip(&self, state: &mut ClusterState) {
+        let peers = self.peers(state);
+        let gossip = ClusterMessage::Gossip(state.gossip.clone());
+        let status = state.gossip.member(self.address()).filter(|member| member.uid == self.self_member.uid)
+                                                        .map(|member| member.status);
+        match status {
+            Some(MemberStatus::Down) | Some(MemberStatus::Removed) => {
+                for peer in peers {
+                    self.transport.send(&peer.address, &gossip);
+                }
+                info!("{} was removed from the cluster", self.address());
+                state.stopped = true;
+            },
+            Some(_) if !peers.is_empty() => {
+                let peer = &peers[rand::thread_rng().gen_range(0, peers.len())];
+                self.transport.send(&peer.address, &gossip);
+            },
+            _ => {},
+        }
+    }
+
+    /// Sends heartbeats to the members, and checks which ones can be reached.
+    fn heartbeat(&self, state: &mut ClusterState) {
+        if !self.is_member(state) {
+            return;
+        }
+        let now = Instant::now();
+        let event_stream = self.actor_system.event_stream();
+        for peer in self.peers(state) {
+            self.transport.send(&peer.address, &ClusterMessage::Heartbeat(self.address().to_owned()));
+            // Members are monitored from the moment they are known, so that a member which never
+            // sends heartbeats becomes unreachable.
+            let reachable = state.failure_detectors.entry(peer.address.clone())
+                .or_insert_with(|| {
+                    let mut failure_detector = self.failure_detector();
+                    failure_detector.heartbeat_at(now);
+                    failure_detector
+                })
+                .is_available_at(now);
+            let unreachable = state.unreachable.contains_key(&peer.address);
+            if !reachable && !unreachable {
+                warn!("{} can't reach {}", self.address(), peer.address);
+                let _ = state.unreachable.insert(peer.address.clone(), now);
+                self.actor_system.distant_node_unreachable(&peer.address);
+                event_stream.publish(UnreachableMember { member: peer });
+            } else if reachable && unreachable {
+                info!("{} can reach {} again", self.address(), peer.address);
+                let _ = state.unreachable.remove(&peer.address);
+                event_stream.publish(ReachableMember { member: peer });
+            }
+        }
+    }
+}
+
+/// Messages of the cluster daemon.
+#[derive(Clone)]
+enum DaemonMessage {
+    Join(Vec<String>),
+    Leave,
+    Down(String),
+    Received(ClusterMessage),
+    GossipTick,
+    HeartbeatTick,
+    Shutdown,
+}
+
+/// Actor running the membership protocol of the node, as `/system/cluster`.
+struct ClusterDaemon {
+    cluster: Cluster,
+}
+
+impl Actor for ClusterDaemon {
+    fn pre_start(&self, context: ActorCell) {
+        let inner = &self.cluster.inner;
+        inner.schedule(&context, inner.settings.gossip_interval, DaemonMessage::GossipTick);
+        inner.schedule(&context, inner.settings.heartbeat_interval, DaemonMessage::HeartbeatTick);
+    }
+
+    fn receive(&self, message: Box<Any>, context: ActorCell) {
+        if let Ok(message) = Box::<Any>::downcast::<DaemonMessage>(message) {
+            self.cluster.inner.handle(*message, &context);
+        }
+    }
+}
+
+impl ClusterDaemon {
+    fn new(cluster: Cluster) -> ClusterDaemon {
+        ClusterDaemon {
+            cluster: cluster,
+        }
+    }
+}
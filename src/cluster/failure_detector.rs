@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Number of intervals between heartbeats kept to compute their distribution.
+const MAX_SAMPLE_SIZE: usize = 1000;
+
+/// Phi accrual failure detector, as described in "The φ Accrual Failure Detector" by Hayashibara
+/// et al.
+///
+/// Instead of saying whether a node is alive after a fixed timeout, it gives a suspicion level
+/// (phi) computed from the distribution of the intervals between the heartbeats it received: the
+/// node is very likely down when phi is high. A phi of 1 means that the chances that the node is
+/// suspected by mistake are about 10%, 2 about 1%, 3 about 0.1% and so on.
+///
+/// The node is considered available while phi is below the threshold, and while no heartbeat was
+/// received at all.
+#[derive(Clone, Debug)]
+pub struct PhiAccrualFailureDetector {
+    threshold: f64,
+    min_std_deviation: Duration,
+    acceptable_heartbeat_pause: Duration,
+    // Intervals between the last heartbeats, in milliseconds.
+    intervals: VecDeque<f64>,
+    last_heartbeat: Option<Instant>,
+}
+
+impl PhiAccrualFailureDetector {
+    /// Creates a failure detector suspecting the node when phi goes above the threshold.
+    ///
+    /// The expected interval between heartbeats is used until heartbeats are received. The
+    /// standard deviation of the intervals is at least `min_std_deviation`, so that regular
+    /// heartbeats do not make the detector too sensitive, and `acceptable_heartbeat_pause` is
+    /// added to their mean, to tolerate pauses such as garbage collections of the sender.
+    pub fn new(threshold: f64,
+               heartbeat_interval: Duration,
+               min_std_deviation: Duration,
+               acceptable_heartbeat_pause: Duration)
+               -> PhiAccrualFailureDetector {
+        // The history starts with two intervals around the expected one, as otherwise the first
+        // heartbeats give no deviation.
+        let interval = millis(heartbeat_interval);
+        let deviation = interval / 4.0;
+        let mut intervals = VecDeque::new();
+        intervals.push_back(interval - deviation);
+        intervals.push_back(interval + deviation);
+        PhiAccrualFailureDetector {
+            threshold: threshold,
+            min_std_deviation: min_std_deviation,
+            acceptable_heartbeat_pause: acceptable_heartbeat_pause,
+            intervals: intervals,
+            last_heartbeat: None,
+        }
+    }
+
+    /// Records a heartbeat received now.
+    pub fn heartbeat(&mut self) {
+        self.heartbeat_at(Instant::now());
+    }
+
+    /// Records a heartbeat received at the given time.
+    pub fn heartbeat_at(&mut self, time: Instant) {
+        if let Some(last_heartbeat) = self.last_heartbeat {
+            if time > last_heartbeat {
+                if self.intervals.len() >= MAX_SAMPLE_SIZE {
+                    let _ = self.intervals.pop_front();
+                }
+                self.intervals.push_back(millis(time - last_heartbeat));
+            }
+        }
+        self.last_heartbeat = Some(time);
+    }
+
+    /// Suspicion level of the node now.
+    pub fn phi(&self) -> f64 {
+        self.phi_at(Instant::now())
+    }
+
+    /// Suspicion level of the node at the given time, 0 if no heartbeat was received.
+    pub fn phi_at(&self, time: Instant) -> f64 {
+        let last_heartbeat = match self.last_heartbeat {
+            Some(last_heartbeat) => last_heartbeat,
+            None => return 0.0,
+        };
+        let elapsed = if time > last_heartbeat { millis(time - last_heartbeat) } else { 0.0 };
+        let count = self.intervals.len() as f64;
+        let mean = self.intervals.iter().sum::<f64>() / count;
+        let variance = self.intervals.iter().map(|interval| (interval - mean) * (interval - mean)).sum::<f64>() / count;
+        let std_deviation = variance.sqrt().max(millis(self.min_std_deviation));
+        phi(elapsed, mean + millis(self.acceptable_heartbeat_pause), std_deviation)
+    }
+
+    /// Tells whether the node is considered available now.
+    pub fn is_available(&self) -> bool {
+        self.is_available_at(Instant::now())
+    }
+
+    /// Tells whether the node is considered available at the given time.
+    pub fn is_available_at(&self, time: Instant) -> bool {
+        self.phi_at(time) < self.threshold
+    }
+}
+
+/// Gives -log10 of the probability that a heartbeat arrives after `elapsed`, for intervals
+/// following a normal distribution, using a logistic approximation of its cumulative distribution.
+fn phi(elapsed: f64, mean: f64, std_deviation: f64) -> f64 {
+    let y = (elapsed - mean) / std_deviation;
+    let e = (-y * (1.5976 + 0.070566 * y * y)).exp();
+    if elapsed > mean {
+        -(e / (1.0 + e)).log10()
+    } else {
+        -(1.0 - 1.0 / (1.0 + e)).log10()
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1000.0 + f64::from(duration.subsec_nanos()) / 1_000_000.0
+}
@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+
+use cluster::member::{Member, MemberStatus};
+
+/// Membership state of the cluster, exchanged by the nodes.
+///
+/// Merging two states keeps, for each address, the member with the latest status (statuses only
+/// move forward), so the nodes agree once they have exchanged their states. Removed members are
+/// kept so that they are not added again by an older state.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Gossip {
+    members: BTreeMap<String, Member>,
+}
+
+impl Gossip {
+    /// Creates an empty membership.
+    pub(crate) fn new() -> Gossip {
+        Gossip {
+            members: BTreeMap::new(),
+        }
+    }
+
+    /// Creates a membership with the given members.
+    pub(crate) fn from_members(members: Vec<Member>) -> Gossip {
+        let mut gossip = Gossip::new();
+        for member in members {
+            gossip.update(member);
+        }
+        gossip
+    }
+
+    /// All the members, including the removed ones, by address.
+    pub(crate) fn all_members(&self) -> Vec<&Member> {
+        self.members.values().collect()
+    }
+
+    /// Members which were not removed, by address.
+    pub(crate) fn members(&self) -> Vec<Member> {
+        self.members.values().filter(|member| member.status != MemberStatus::Removed).cloned().collect()
+    }
+
+    /// Member with the given address, removed or not.
+    pub(crate) fn member(&self, address: &str) -> Option<&Member> {
+        self.members.get(address)
+    }
+
+    /// Merges a member in the membership, and tells whether it changed.
+    pub(crate) fn update(&mut self, member: Member) -> bool {
+        let merged = match self.members.get(&member.address) {
+            Some(known) => merge(known, &member),
+            None => member,
+        };
+        let changed = self.members.get(&merged.address) != Some(&merged);
+        if changed {
+            let _ = self.members.insert(merged.address.clone(), merged);
+        }
+        changed
+    }
+
+    /// Merges another state in this one, and tells whether it changed.
+    pub(crate) fn merge(&mut self, other: &Gossip) -> bool {
+        let mut changed = false;
+        for member in other.members.values() {
+            changed |= self.update(member.clone());
+        }
+        changed
+    }
+
+    /// Up number to give to the next member becoming up.
+    pub(crate) fn next_up_number(&self) -> u64 {
+        self.members.values().map(|member| member.up_number).max().unwrap_or(0) + 1
+    }
+}
+
+/// Gives the latest of two versions of a member with the same address.
+///
+/// Between two incarnations of a node the one which was not removed wins, and otherwise the one
+/// with the highest uid.
+fn merge(first: &Member, second: &Member) -> Member {
+    if first.uid == second.uid {
+        let mut merged = first.clone();
+        merged.status = first.status.max(second.status);
+        merged.up_number = first.up_number.max(second.up_number);
+        return merged;
+    }
+    let removed = |member: &Member| member.status == MemberStatus::Removed;
+    let first_wins = match (removed(first), removed(second)) {
+        (false, true) => true,
+        (true, false) => false,
+        _ => first.uid > second.uid,
+    };
+    if first_wins { first.clone() } else { second.clone() }
+}
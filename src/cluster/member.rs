@@ -0,0 +1,126 @@
+use std::any::Any;
+use std::cmp::Ordering;
+
+/// Status of a member of the cluster.
+///
+/// A member goes through these statuses in order, it can be downed at any time before being
+/// removed.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum MemberStatus {
+    /// The node asked to join the cluster, and waits for the leader to make it up.
+    Joining,
+    /// The node is a full member of the cluster.
+    Up,
+    /// The node asked to leave the cluster.
+    Leaving,
+    /// The leader acknowledged that the node leaves, it will then be removed.
+    Exiting,
+    /// The node was declared down, it will be removed without leaving gracefully.
+    Down,
+    /// The node is no longer a member of the cluster.
+    Removed,
+}
+
+/// A node of the cluster, as known by the membership gossip.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Member {
+    /// Address and port of the node, such as `127.0.0.1:12345`.
+    pub address: String,
+    /// Unique id of the incarnation of the node, a node started again on the same address gets a
+    /// new uid.
+    pub uid: u64,
+    /// Status of the member.
+    pub status: MemberStatus,
+    /// Order in which the members became up, starting at 1, 0 if the member was never up.
+    pub up_number: u64,
+}
+
+impl Member {
+    /// Creates a joining member.
+    pub(crate) fn new(address: String, uid: u64) -> Member {
+        Member {
+            address: address,
+            uid: uid,
+            status: MemberStatus::Joining,
+            up_number: 0,
+        }
+    }
+
+    /// Tells whether the member became up before the other one.
+    ///
+    /// Members which were never up are younger than all the others, ties are broken by address.
+    pub fn is_older_than(&self, other: &Member) -> bool {
+        let age = |member: &Member| if member.up_number == 0 { u64::max_value() } else { member.up_number };
+        match age(self).cmp(&age(other)) {
+            Ordering::Equal => self.address < other.address,
+            ordering => ordering == Ordering::Less,
+        }
+    }
+}
+
+/// Class of the events published by the cluster on the EventStream of the actor system,
+/// subscribing to `TypeId::of::<ClusterEvent>()` gives all of them.
+#[derive(Clone, Copy)]
+pub enum ClusterEvent {}
+
+/// Published when a node asks to join the cluster.
+#[derive(Clone, Debug)]
+pub struct MemberJoined {
+    /// The joining member.
+    pub member: Member,
+}
+
+/// Published when a member becomes up.
+#[derive(Clone, Debug)]
+pub struct MemberUp {
+    /// The member that is now up.
+    pub member: Member,
+}
+
+/// Published when a member asks to leave the cluster.
+#[derive(Clone, Debug)]
+pub struct MemberLeft {
+    /// The leaving member.
+    pub member: Member,
+}
+
+/// Published when the leader acknowledges that a member leaves.
+#[derive(Clone, Debug)]
+pub struct MemberExited {
+    /// The exiting member.
+    pub member: Member,
+}
+
+/// Published when a member is declared down.
+#[derive(Clone, Debug)]
+pub struct MemberDowned {
+    /// The member that is down.
+    pub member: Member,
+}
+
+/// Published when a member is removed from the cluster.
+#[derive(Clone, Debug)]
+pub struct MemberRemoved {
+    /// The removed member.
+    pub member: Member,
+}
+
+/// Published when the failure detector of this node suspects a member.
+#[derive(Clone, Debug)]
+pub struct UnreachableMember {
+    /// The member which can't be reached.
+    pub member: Member,
+}
+
+/// Published when a member that was unreachable can be reached again.
+#[derive(Clone, Debug)]
+pub struct ReachableMember {
+    /// The member which can be reached again.
+    pub member: Member,
+}
+
+/// Tells whether the event is one of those changing the set of up members.
+pub(crate) fn changes_up_members(event: &Any) -> bool {
+    event.is::<MemberUp>() || event.is::<MemberLeft>() || event.is::<MemberExited>() || event.is::<MemberDowned>() ||
+    event.is::<MemberRemoved>()
+}
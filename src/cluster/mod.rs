@@ -0,0 +1,30 @@
+pub use self::cluster::{Cluster, ClusterSettings};
+pub use self::failure_detector::PhiAccrualFailureDetector;
+pub use self::singleton::{ClusterSingletonManager, ClusterSingletonProxy};
+pub use self::sharding::{EntityId, ShardRegion, ShardRegionMessage, ShardingSettings};
+pub use self::member::{ClusterEvent, Member, MemberDowned, MemberExited, MemberJoined, MemberLeft, MemberRemoved,
+                       MemberStatus, MemberUp, ReachableMember, UnreachableMember};
+
+/// Module with the Cluster, the membership of an actor system in a cluster.
+mod cluster;
+
+/// Module with the failure detector monitoring the heartbeats of the members.
+mod failure_detector;
+
+/// Module with the membership state spread by gossip.
+mod gossip;
+
+/// Module with the members of the cluster and the membership events.
+mod member;
+
+/// Module with the messages exchanged by the nodes, and their serialization.
+mod protocol;
+
+/// Module with the ShardRegions, spreading entity actors over the nodes.
+mod sharding;
+
+/// Module with the singleton actors of the cluster, running on its oldest member.
+mod singleton;
+
+/// Module with the TCP transport of the messages of the cluster.
+mod transport;
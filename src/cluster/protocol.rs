@@ -0,0 +1,163 @@
+use actors::RobotsError;
+use cluster::gossip::Gossip;
+use cluster::member::{Member, MemberStatus};
+use persistence::Persist;
+
+/// Messages exchanged by the nodes of a cluster.
+#[derive(Clone, Debug)]
+pub(crate) enum ClusterMessage {
+    /// Sent by a node to the seed nodes until it is part of the membership.
+    Join(Member),
+    /// The membership state known by the sender.
+    Gossip(Gossip),
+    /// Sent regularly to the other members, with the address of the sender.
+    Heartbeat(String),
+    /// Serialized message for the actor with the given logical path.
+    Deliver(String, Vec<u8>),
+}
+
+/// Message given to a local actor for a `ClusterMessage::Deliver`, the actor deserializes it.
+#[derive(Clone, Debug)]
+pub(crate) struct RemotePayload(pub(crate) Vec<u8>);
+
+// Kinds of the messages on the wire.
+const JOIN: u8 = 0;
+const GOSSIP: u8 = 1;
+const HEARTBEAT: u8 = 2;
+const DELIVER: u8 = 3;
+
+impl ClusterMessage {
+    /// Serializes the message, with its length first.
+    pub(crate) fn to_frame(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        match *self {
+            ClusterMessage::Join(ref member) => {
+                payload.push(JOIN);
+                write_member(&mut payload, member);
+            },
+            ClusterMessage::Gossip(ref gossip) => {
+                payload.push(GOSSIP);
+                let members = gossip.all_members();
+                payload.extend((members.len() as u32).to_bytes());
+                for member in members {
+                    write_member(&mut payload, member);
+                }
+            },
+            ClusterMessage::Heartbeat(ref address) => {
+                payload.push(HEARTBEAT);
+                write_string(&mut payload, address);
+            },
+            ClusterMessage::Deliver(ref path, ref message) => {
+                payload.push(DELIVER);
+                write_string(&mut payload, path);
+                payload.extend_from_slice(message);
+            },
+        }
+        let mut frame = (payload.len() as u32).to_bytes();
+        frame.extend(payload);
+        frame
+    }
+
+    /// Deserializes a message from a frame without its length.
+    pub(crate) fn from_payload(payload: &[u8]) -> Result<ClusterMessage, RobotsError> {
+        let mut reader = Reader::new(payload);
+        let message = match reader.read_u8()? {
+            JOIN => ClusterMessage::Join(reader.read_member()?),
+            GOSSIP => {
+                let mut members = Vec::new();
+                for _ in 0..reader.read_u32()? {
+                    members.push(reader.read_member()?);
+                }
+                ClusterMessage::Gossip(Gossip::from_members(members))
+            },
+            HEARTBEAT => ClusterMessage::Heartbeat(reader.read_string()?),
+            DELIVER => {
+                let path = reader.read_string()?;
+                ClusterMessage::Deliver(path, reader.read_to_end().to_vec())
+            },
+            kind => return Err(RobotsError::Serialization(format!("unknown cluster message {}", kind))),
+        };
+        Ok(message)
+    }
+}
+
+fn write_string(buffer: &mut Vec<u8>, string: &str) {
+    buffer.extend((string.len() as u32).to_bytes());
+    buffer.extend_from_slice(string.as_bytes());
+}
+
+fn write_member(buffer: &mut Vec<u8>, member: &Member) {
+    write_string(buffer, &member.address);
+    buffer.extend(member.uid.to_bytes());
+    buffer.push(member.status as u8);
+    buffer.extend(member.up_number.to_bytes());
+}
+
+/// Reads the values written in a message, in order.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader {
+            bytes: bytes,
+            offset: 0,
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], RobotsError> {
+        match self.bytes.get(self.offset..self.offset + len) {
+            Some(bytes) => {
+                self.offset += len;
+                Ok(bytes)
+            },
+            None => Err(RobotsError::Serialization("truncated cluster message".to_owned())),
+        }
+    }
+
+    fn read_to_end(&mut self) -> &'a [u8] {
+        let bytes = &self.bytes[self.offset..];
+        self.offset = self.bytes.len();
+        bytes
+    }
+
+    fn read_u8(&mut self) -> Result<u8, RobotsError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, RobotsError> {
+        u32::from_bytes(self.take(4)?)
+    }
+
+    fn read_u64(&mut self) -> Result<u64, RobotsError> {
+        u64::from_bytes(self.take(8)?)
+    }
+
+    fn read_string(&mut self) -> Result<String, RobotsError> {
+        let len = self.read_u32()? as usize;
+        String::from_bytes(self.take(len)?)
+    }
+
+    fn read_member(&mut self) -> Result<Member, RobotsError> {
+        let address = self.read_string()?;
+        let uid = self.read_u64()?;
+        let status = match self.read_u8()? {
+            0 => MemberStatus::Joining,
+            1 => MemberStatus::Up,
+            2 => MemberStatus::Leaving,
+            3 => MemberStatus::Exiting,
+            4 => MemberStatus::Down,
+            5 => MemberStatus::Removed,
+            status => return Err(RobotsError::Serialization(format!("unknown member status {}", status))),
+        };
+        let up_number = self.read_u64()?;
+        Ok(Member {
+            address: address,
+            uid: uid,
+            status: status,
+            up_number: up_number,
+        })
+    }
+}
@@ -0,0 +1,354 @@
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actors::{Actor, ActorCell, ActorContext, ActorRef, InnerMessage, Message, Props, RobotsError};
+use actors::props::ActorFactory;
+use cluster::cluster::Cluster;
+use cluster::member::{self, ClusterEvent};
+use cluster::protocol::RemotePayload;
+use persistence::Persist;
+
+/// Closure giving the id of the entity a message is for.
+///
+/// The id is the name of the entity actor, so it must be a valid name: messages whose id contains
+/// a `/` or starts with a `$` are dropped.
+pub type EntityId<M> = Arc<Fn(&M) -> String + Send + Sync>;
+
+/// Number of times a message can be sent to another node before it is kept until the nodes agree
+/// on the owner of its shard.
+const MAX_HOPS: u8 = 3;
+
+/// Number of messages kept by a ShardRegion while their shard has no owner, the next ones are
+/// dropped.
+const MAX_BUFFERED: usize = 10_000;
+
+/// Settings of the ShardRegions of a type of entities.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShardingSettings {
+    /// Number of shards the entities are spread over, it must be the same on all the nodes.
+    ///
+    /// Shards are the unit moved between nodes, there should be several times more shards than
+    /// nodes.
+    pub number_of_shards: u64,
+    /// Time without messages after which an entity is stopped, never if `None`.
+    pub passivate_idle_after: Option<Duration>,
+    /// Interval between two attempts to send the messages which could not be delivered, and
+    /// between two checks of the idle entities.
+    pub retry_interval: Duration,
+}
+
+impl ShardingSettings {
+    /// Creates the settings for the given number of shards, without passivation.
+    pub fn new(number_of_shards: u64) -> ShardingSettings {
+        ShardingSettings {
+            number_of_shards: number_of_shards,
+            passivate_idle_after: None,
+            retry_interval: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Messages understood by a ShardRegion, in addition to the messages for its entities.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShardRegionMessage {
+    /// Sent by an entity to its region (its father) to be stopped, for example when it has been
+    /// idle for a while. It is created again when a message comes for it.
+    Passivate,
+    /// Completes the sending Future with a Vec<String> of the ids of the entities running on this
+    /// node, sorted.
+    GetEntities,
+}
+
+/// Periodic message of a ShardRegion, to retry the buffered messages and passivate idle entities.
+#[derive(Clone, Copy, Debug)]
+struct RegionTick;
+
+/// Entity running on this node.
+struct Entity {
+    actor: ActorRef,
+    last_message: Instant,
+}
+
+struct RegionState<M> {
+    entities: HashMap<String, Entity>,
+    // Messages waiting for the owner of their shard, with their sender.
+    buffer: VecDeque<(M, ActorRef)>,
+}
+
+/// Actor spreading entity actors over the nodes of a cluster, and sending them the messages of
+/// type `M`.
+///
+/// Each message carries the id of an entity, given by the `entity_id` closure. Entities are
+/// grouped in shards by the hash of their id, and each shard is owned by one of the up members of
+/// the cluster, chosen by rendezvous hashing so that only the shards of the nodes that come or go
+/// change owner. A ShardRegion must be created with the same name and settings on every node:
+/// messages for shards of other nodes are sent to the region with the same path there, which
+/// creates the entities of its shards on demand, as its children named after their ids.
+///
+/// When the members change, the entities of the shards that moved to another node are stopped,
+/// and are created again on their new node by the next message for them. Messages sent while no
+/// node is up, or while the nodes disagree on the owner of a shard, are kept and sent again every
+/// `retry_interval`.
+///
+/// Messages for entities of other nodes are serialized, which is why `M` must be `Persist`, and
+/// lose their sender on the way: entities can only answer the messages sent from their own node.
+/// Entities can ask to be stopped with `ShardRegionMessage::Passivate`, and are stopped after
+/// `passivate_idle_after` without messages.
+pub struct ShardRegion<M: Message + Persist> {
+    cluster: Cluster,
+    entity_props: Arc<ActorFactory>,
+    settings: ShardingSettings,
+    entity_id: EntityId<M>,
+    state: Mutex<RegionState<M>>,
+}
+
+impl<M: Message + Persist> ShardRegion<M> {
+    /// Gives Props for a ShardRegion creating its entities with the `entity_props`, the messages
+    /// going to the entity with the id given by `entity_id`.
+    pub fn props<F>(cluster: Cluster,
+                    entity_props: Arc<ActorFactory>,
+                    settings: ShardingSettings,
+                    entity_id: F)
+                    -> Arc<ActorFactory>
+        where F: Fn(&M) -> String + Send + Sync + 'static
+    {
+        let entity_id: EntityId<M> = Arc::new(entity_id);
+        Props::new(Arc::new(ShardRegion::<M>::new), (cluster, entity_props, settings, entity_id))
+    }
+
+    fn new((cluster, entity_props, settings, entity_id): (Cluster, Arc<ActorFactory>, ShardingSettings, EntityId<M>))
+           -> ShardRegion<M> {
+        ShardRegion {
+            cluster: cluster,
+            entity_props: entity_props,
+            settings: settings,
+            entity_id: entity_id,
+            state: Mutex::new(RegionState {
+                entities: HashMap::new(),
+                buffer: VecDeque::new(),
+            }),
+        }
+    }
+
+    fn shard(&self, entity_id: &str) -> u64 {
+        hash(entity_id.as_bytes()) % self.settings.number_of_shards.max(1)
+    }
+
+    /// Gives the address of the up member owning the shard, the one with the highest hash of the
+    /// shard followed by its address.
+    fn owner(&self, shard: u64) -> Option<String> {
+        self.cluster
+            .up_members()
+            .into_iter()
+            .max_by_key(|member| {
+                let mut key = shard.to_bytes();
+                key.extend_from_slice(member.address.as_bytes());
+                hash(&key)
+            })
+            .map(|member| member.address)
+    }
+
+    /// Sends the message to its entity, here or on the node owning its shard.
+    fn route(&self, message: M, sender: ActorRef, hops: u8, context: &ActorCell) {
+        let entity_id = (self.entity_id)(&message);
+        let owner = match self.owner(self.shard(&entity_id)) {
+            Some(owner) => owner,
+            None => return self.buffer(message, sender, context),
+        };
+        if owner == self.cluster.address() {
+            self.deliver(entity_id, message, sender, context);
+        } else if hops < MAX_HOPS {
+            let mut envelope = vec![hops + 1];
+            envelope.extend(message.to_bytes());
+            self.cluster.deliver(&owner, context.path().logical_path(), envelope);
+        } else {
+            self.buffer(message, sender, context);
+        }
+    }
+
+    /// Gives the message to the local entity, which is created if needed.
+    ///
+    /// The state is not locked while creating the entity or giving it the message, as both may
+    /// run code of the entity.
+    fn deliver(&self, entity_id: String, message: M, sender: ActorRef, context: &ActorCell) {
+        let entity = self.state.lock().unwrap().entities.get(&entity_id).map(|entity| entity.actor.clone());
+        let actor = match entity {
+            Some(ref actor) if actor.is_alive() => actor.clone(),
+            _ => match context.actor_of(self.entity_props.clone(), entity_id.clone()) {
+                Ok(actor) => actor,
+                Err(RobotsError::InvalidName(_)) => {
+                    return warn!("{} dropped a message for the entity {}, which is not a valid actor name",
+                                 context.path().logical_path(), entity_id);
+                },
+                Err(error) => {
+                    // The previous entity with this id may still be stopping.
+                    debug!("{} could not create the entity {}: {}", context.path().logical_path(), entity_id, error);
+                    return self.buffer(message, sender, context);
+                },
+            },
+        };
+        let _ = self.state.lock().unwrap().entities.insert(entity_id, Entity {
+            actor: actor.clone(),
+            last_message: Instant::now(),
+        });
+        let _ = actor.receive(InnerMessage::Message(Box::new(message)), sender);
+    }
+
+    fn buffer(&self, message: M, sender: ActorRef, context: &ActorCell) {
+        let mut state = self.state.lock().unwrap();
+        if state.buffer.len() >= MAX_BUFFERED {
+            return warn!("{} has too many messages waiting, a message is dropped", context.path().logical_path());
+        }
+        state.buffer.push_back((message, sender));
+    }
+
+    /// Sends the buffered messages again.
+    fn retry(&self, context: &ActorCell) {
+        let buffer: Vec<(M, ActorRef)> = self.state.lock().unwrap().buffer.drain(..).collect();
+        for (message, sender) in buffer {
+            self.route(message, sender, 0, context);
+        }
+    }
+
+    /// Stops the entities of the shards owned by another node now.
+    fn rebalance(&self, context: &ActorCell) {
+        let address = self.cluster.address();
+        let moved: Vec<String> = self.state
+            .lock()
+            .unwrap()
+            .entities
+            .keys()
+            .filter(|entity_id| self.owner(self.shard(entity_id)).map_or(true, |owner| owner != address))
+            .cloned()
+            .collect();
+        for entity_id in moved {
+            self.stop_entity(&entity_id, context);
+        }
+    }
+
+    /// Stops the entities which did not get a message for `passivate_idle_after`.
+    fn passivate_idle(&self, context: &ActorCell) {
+        let idle_after = match self.settings.passivate_idle_after {
+            Some(idle_after) => idle_after,
+            None => return,
+        };
+        let idle: Vec<String> = self.state
+            .lock()
+            .unwrap()
+            .entities
+            .iter()
+            .filter(|&(_, entity)| entity.last_message.elapsed() >= idle_after)
+            .map(|(entity_id, _)| entity_id.clone())
+            .collect();
+        for entity_id in idle {
+            self.stop_entity(&entity_id, context);
+        }
+    }
+
+    fn stop_entity(&self, entity_id: &str, context: &ActorCell) {
+        let entity = self.state.lock().unwrap().entities.remove(entity_id);
+        if let Some(entity) = entity {
+            context.stop(entity.actor);
+        }
+    }
+
+    /// Ids of the entities still running, sorted.
+    fn entities(&self) -> Vec<String> {
+        let entities: Vec<(String, ActorRef)> = self.state
+            .lock()
+            .unwrap()
+            .entities
+            .iter()
+            .map(|(entity_id, entity)| (entity_id.clone(), entity.actor.clone()))
+            .collect();
+        let mut entities: Vec<String> = entities.into_iter()
+                                                .filter(|&(_, ref actor)| actor.is_alive())
+                                                .map(|(entity_id, _)| entity_id)
+                                                .collect();
+        entities.sort();
+        entities
+    }
+
+    fn schedule_tick(&self, context: &ActorCell) {
+        self.cluster.actor_system().schedule_once(self.settings.retry_interval, context.actor_ref(), RegionTick);
+    }
+}
+
+impl<M: Message + Persist> Actor for ShardRegion<M> {
+    fn pre_start(&self, context: ActorCell) {
+        // When the region is restarted its entities are still there.
+        {
+            let mut state = self.state.lock().unwrap();
+            for child in context.children().values() {
+                let entity_id = child.path().logical_path().rsplit('/').next().unwrap().to_owned();
+                let _ = state.entities.insert(entity_id, Entity {
+                    actor: child.clone(),
+                    last_message: Instant::now(),
+                });
+            }
+        }
+        context.subscribe(TypeId::of::<ClusterEvent>());
+        self.schedule_tick(&context);
+    }
+
+    fn receive(&self, message: Box<Any>, context: ActorCell) {
+        let message = match Box::<Any>::downcast::<M>(message) {
+            Ok(message) => return self.route(*message, context.sender(), 0, &context),
+            Err(message) => message,
+        };
+        let message = match Box::<Any>::downcast::<RemotePayload>(message) {
+            Ok(payload) => {
+                let RemotePayload(bytes) = *payload;
+                match bytes.split_first().map(|(&hops, bytes)| (hops, M::from_bytes(bytes))) {
+                    Some((hops, Ok(message))) => self.route(message, context.sender(), hops, &context),
+                    _ => warn!("{} received an invalid message from another node", context.path().logical_path()),
+                }
+                return;
+            },
+            Err(message) => message,
+        };
+        let message = match Box::<Any>::downcast::<ShardRegionMessage>(message) {
+            Ok(message) => {
+                match *message {
+                    ShardRegionMessage::Passivate => {
+                        let sender = context.sender();
+                        let entity_id = self.state
+                            .lock()
+                            .unwrap()
+                            .entities
+                            .iter()
+                            .find(|&(_, entity)| entity.actor.path().logical_path() == sender.path().logical_path())
+                            .map(|(entity_id, _)| entity_id.clone());
+                        if let Some(entity_id) = entity_id {
+                            self.stop_entity(&entity_id, &context);
+                        }
+                    },
+                    ShardRegionMessage::GetEntities => context.complete(context.sender(), self.entities()),
+                }
+                return;
+            },
+            Err(message) => message,
+        };
+        if message.is::<RegionTick>() {
+            self.passivate_idle(&context);
+            self.retry(&context);
+            self.schedule_tick(&context);
+        } else if member::changes_up_members(&*message) {
+            // The set of up members changed, so some shards have a new owner.
+            self.rebalance(&context);
+            self.retry(&context);
+        }
+    }
+}
+
+/// 64 bits FNV-1a hash of the bytes.
+///
+/// All the nodes must agree on the owner of each shard, so the hash is written out here rather
+/// than given by `DefaultHasher`, whose algorithm may change between builds and versions of Rust.
+fn hash(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(PRIME))
+}
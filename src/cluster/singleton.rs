@@ -0,0 +1,234 @@
+use std::any::{Any, TypeId};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actors::{Actor, ActorCell, ActorContext, ActorRef, InnerMessage, Message, Props};
+use actors::props::ActorFactory;
+use cluster::cluster::Cluster;
+use cluster::member::{self, ClusterEvent, Member, MemberStatus};
+use cluster::protocol::RemotePayload;
+use persistence::Persist;
+
+/// Interval between two checks of where the singleton should run, and between two attempts to
+/// send the buffered messages.
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Number of times a message can be sent to another node before it is kept until the nodes agree
+/// on where the singleton runs.
+const MAX_HOPS: u8 = 3;
+
+/// Number of messages kept by a proxy while the singleton is not running, the next ones are
+/// dropped.
+const MAX_BUFFERED: usize = 10_000;
+
+/// Periodic message of the singleton managers and proxies.
+#[derive(Clone, Copy, Debug)]
+struct SingletonTick;
+
+/// Gives the member where the singleton runs, the oldest up member, unless an older member is
+/// leaving: the singleton is then handed over, and runs nowhere until that member exits.
+fn singleton_member(cluster: &Cluster) -> Option<Member> {
+    cluster.members()
+           .into_iter()
+           .filter(|member| member.status == MemberStatus::Up || member.status == MemberStatus::Leaving)
+           .fold(None, |oldest: Option<Member>, member| match oldest {
+               Some(ref oldest) if oldest.is_older_than(&member) => Some(oldest.clone()),
+               _ => Some(member),
+           })
+           .and_then(|member| if member.status == MemberStatus::Up { Some(member) } else { None })
+}
+
+/// Actor running a singleton actor of the cluster when its node is the oldest up member.
+///
+/// A ClusterSingletonManager must be created with the same name on every node which can run the
+/// singleton. The singleton is its child with the given name, created with the `singleton_props`,
+/// and is stopped when the node leaves the cluster or is no longer the oldest member. The next
+/// oldest member only starts it once the previous one exited, so that there is one singleton in
+/// the cluster as long as the nodes agree on its membership.
+///
+/// Messages are sent to the singleton through a `ClusterSingletonProxy`.
+pub struct ClusterSingletonManager {
+    cluster: Cluster,
+    singleton_name: String,
+    singleton_props: Arc<ActorFactory>,
+}
+
+impl ClusterSingletonManager {
+    /// Gives Props for a ClusterSingletonManager running the singleton created with the
+    /// `singleton_props` as its child named `singleton_name`.
+    pub fn props(cluster: Cluster, singleton_name: String, singleton_props: Arc<ActorFactory>) -> Arc<ActorFactory> {
+        Props::new(Arc::new(ClusterSingletonManager::new), (cluster, singleton_name, singleton_props))
+    }
+
+    fn new((cluster, singleton_name, singleton_props): (Cluster, String, Arc<ActorFactory>)) -> ClusterSingletonManager {
+        ClusterSingletonManager {
+            cluster: cluster,
+            singleton_name: singleton_name,
+            singleton_props: singleton_props,
+        }
+    }
+
+    fn singleton(&self, context: &ActorCell) -> Option<ActorRef> {
+        context.children()
+               .values()
+               .find(|child| child.path().logical_path().rsplit('/').next() == Some(&self.singleton_name))
+               .cloned()
+    }
+
+    /// Starts or stops the singleton depending on where it should run.
+    fn check(&self, context: &ActorCell) {
+        let runs_here = singleton_member(&self.cluster).map_or(false, |member| member.address == self.cluster.address());
+        match self.singleton(context) {
+            Some(singleton) => {
+                if !runs_here {
+                    info!("{} hands over the singleton", context.path().logical_path());
+                    context.stop(singleton);
+                }
+            },
+            None if runs_here => {
+                // The name may still be taken by the previous singleton, it is tried again later.
+                if let Err(error) = context.actor_of(self.singleton_props.clone(), self.singleton_name.clone()) {
+                    debug!("{} could not start the singleton: {}", context.path().logical_path(), error);
+                }
+            },
+            None => {},
+        }
+    }
+
+    fn schedule_tick(&self, context: &ActorCell) {
+        self.cluster.actor_system().schedule_once(RETRY_INTERVAL, context.actor_ref(), SingletonTick);
+    }
+}
+
+impl Actor for ClusterSingletonManager {
+    fn pre_start(&self, context: ActorCell) {
+        context.subscribe(TypeId::of::<ClusterEvent>());
+        self.check(&context);
+        self.schedule_tick(&context);
+    }
+
+    fn receive(&self, message: Box<Any>, context: ActorCell) {
+        if message.is::<SingletonTick>() {
+            self.check(&context);
+            self.schedule_tick(&context);
+        } else if member::changes_up_members(&*message) {
+            self.check(&context);
+        }
+    }
+}
+
+/// Actor sending the messages of type `M` it receives to a singleton of the cluster, wherever it
+/// runs.
+///
+/// A ClusterSingletonProxy must be created with the same name on every node running a
+/// `ClusterSingletonManager`, as messages for the singleton of another node go through the proxy
+/// there. Messages are kept while the singleton is not running, for example while it is handed
+/// over to another node, and sent once it runs again.
+///
+/// Messages for a singleton on another node are serialized, which is why `M` must be `Persist`,
+/// and lose their sender on the way. The singleton gets the messages sent from its own node with
+/// their original sender.
+pub struct ClusterSingletonProxy<M: Message + Persist> {
+    cluster: Cluster,
+    singleton_path: String,
+    // Messages waiting for the singleton, with their sender.
+    buffer: Mutex<VecDeque<(M, ActorRef)>>,
+}
+
+impl<M: Message + Persist> ClusterSingletonProxy<M> {
+    /// Gives Props for a ClusterSingletonProxy sending to the singleton with the given logical
+    /// path on its node, such as `/user/manager/singleton`.
+    pub fn props(cluster: Cluster, singleton_path: String) -> Arc<ActorFactory> {
+        Props::new(Arc::new(ClusterSingletonProxy::<M>::new), (cluster, singleton_path))
+    }
+
+    fn new((cluster, singleton_path): (Cluster, String)) -> ClusterSingletonProxy<M> {
+        ClusterSingletonProxy {
+            cluster: cluster,
+            singleton_path: singleton_path,
+            buffer: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Sends the message to the singleton, here or through the proxy of its node.
+    fn route(&self, message: M, sender: ActorRef, hops: u8, context: &ActorCell) {
+        let address = match singleton_member(&self.cluster) {
+            Some(member) => member.address,
+            None => return self.buffer(message, sender, context),
+        };
+        if address == self.cluster.address() {
+            match context.lookup(&self.singleton_path) {
+                Some(singleton) => {
+                    let _ = singleton.receive(InnerMessage::Message(Box::new(message)), sender);
+                },
+                // The manager did not start it yet.
+                None => self.buffer(message, sender, context),
+            }
+        } else if hops < MAX_HOPS {
+            let mut envelope = vec![hops + 1];
+            envelope.extend(message.to_bytes());
+            self.cluster.deliver(&address, context.path().logical_path(), envelope);
+        } else {
+            self.buffer(message, sender, context);
+        }
+    }
+
+    /// Routes the message, unless messages are waiting: it then waits behind them so that the
+    /// singleton gets them in order, they are all sent again on the next tick.
+    fn send(&self, message: M, sender: ActorRef, hops: u8, context: &ActorCell) {
+        if self.buffer.lock().unwrap().is_empty() {
+            self.route(message, sender, hops, context);
+        } else {
+            self.buffer(message, sender, context);
+        }
+    }
+
+    fn buffer(&self, message: M, sender: ActorRef, context: &ActorCell) {
+        let mut buffer = self.buffer.lock().unwrap();
+        if buffer.len() >= MAX_BUFFERED {
+            return warn!("{} has too many messages waiting, a message is dropped", context.path().logical_path());
+        }
+        buffer.push_back((message, sender));
+    }
+
+    /// Sends the buffered messages again.
+    fn retry(&self, context: &ActorCell) {
+        let buffer: Vec<(M, ActorRef)> = self.buffer.lock().unwrap().drain(..).collect();
+        for (message, sender) in buffer {
+            self.route(message, sender, 0, context);
+        }
+    }
+
+    fn schedule_tick(&self, context: &ActorCell) {
+        self.cluster.actor_system().schedule_once(RETRY_INTERVAL, context.actor_ref(), SingletonTick);
+    }
+}
+
+impl<M: Message + Persist> Actor for ClusterSingletonProxy<M> {
+    fn pre_start(&self, context: ActorCell) {
+        self.schedule_tick(&context);
+    }
+
+    fn receive(&self, message: Box<Any>, context: ActorCell) {
+        let message = match Box::<Any>::downcast::<M>(message) {
+            Ok(message) => return self.send(*message, context.sender(), 0, &context),
+            Err(message) => message,
+        };
+        match Box::<Any>::downcast::<RemotePayload>(message) {
+            Ok(payload) => {
+                let RemotePayload(bytes) = *payload;
+                match bytes.split_first().map(|(&hops, bytes)| (hops, M::from_bytes(bytes))) {
+                    Some((hops, Ok(message))) => self.send(message, context.sender(), hops, &context),
+                    _ => warn!("{} received an invalid message from another node", context.path().logical_path()),
+                }
+            },
+            Err(message) => {
+                if message.is::<SingletonTick>() {
+                    self.retry(&context);
+                    self.schedule_tick(&context);
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use actors::RobotsError;
+use cluster::protocol::ClusterMessage;
+use persistence::Persist;
+
+/// Time given to connect to a node or to write to it.
+const CONNECTION_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Largest message accepted from another node, in bytes. A connection announcing a larger message
+/// is closed, so that a node can't be made to allocate any amount of memory.
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Closure given the messages received from the other nodes.
+pub(crate) type MessageHandler = Arc<Fn(ClusterMessage) + Send + Sync>;
+
+/// Sends and receives the messages of the cluster over TCP.
+///
+/// Each node listens on its address, and has a connection to each node it sends messages to,
+/// written by a thread of its own so that slow or dead nodes do not block the sender. Messages to
+/// a node that can't be reached are dropped, the cluster protocol sends its messages again
+/// anyway.
+pub(crate) struct Transport {
+    address: String,
+    peers: Mutex<HashMap<String, Sender<Vec<u8>>>>,
+    // Accepted connections, closed on shutdown.
+    connections: Arc<Mutex<Vec<TcpStream>>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Transport {
+    /// Listens on the given address, such as `127.0.0.1:0`, and gives the received messages to
+    /// the handler.
+    pub(crate) fn bind(address: &str, handler: MessageHandler) -> Result<Transport, RobotsError> {
+        let listener = TcpListener::bind(address).map_err(network_error)?;
+        let address = listener.local_addr().map_err(network_error)?.to_string();
+        let connections = Arc::new(Mutex::new(Vec::new()));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let (thread_connections, thread_stopped) = (connections.clone(), stopped.clone());
+        let _ = thread::spawn(move || Transport::accept(listener, handler, thread_connections, thread_stopped));
+        Ok(Transport {
+            address: address,
+            peers: Mutex::new(HashMap::new()),
+            connections: connections,
+            stopped: stopped,
+        })
+    }
+
+    /// Address and port the transport listens on.
+    pub(crate) fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Sends the message to the node with the given address.
+    pub(crate) fn send(&self, address: &str, message: &ClusterMessage) {
+        if self.stopped.load(Ordering::SeqCst) {
+            return;
+        }
+        let frame = message.to_frame();
+        if frame.len() - 4 > MAX_FRAME_SIZE {
+            return warn!("A message of {} bytes is too large to be sent to {}", frame.len() - 4, address);
+        }
+        let mut peers = self.peers.lock().unwrap();
+        if let Some(peer) = peers.get(address) {
+            if peer.send(frame.clone()).is_ok() {
+                return;
+            }
+        }
+        let (tx, rx) = channel();
+        let peer_address = address.to_owned();
+        let _ = thread::spawn(move || Transport::write(peer_address, rx));
+        let _ = tx.send(frame);
+        let _ = peers.insert(address.to_owned(), tx);
+    }
+
+    /// Closes the connection to the node with the given address, if any.
+    pub(crate) fn disconnect(&self, address: &str) {
+        let _ = self.peers.lock().unwrap().remove(address);
+    }
+
+    /// Stops listening and closes all the connections.
+    pub(crate) fn shutdown(&self) {
+        if self.stopped.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        self.peers.lock().unwrap().clear();
+        for connection in self.connections.lock().unwrap().drain(..) {
+            let _ = connection.shutdown(Shutdown::Both);
+        }
+        // Wakes the listener thread up so that it sees that the transport is stopped.
+        let _ = TcpStream::connect_timeout(&self.address.parse().unwrap(), CONNECTION_TIMEOUT);
+    }
+
+    fn accept(listener: TcpListener,
+              handler: MessageHandler,
+              connections: Arc<Mutex<Vec<TcpStream>>>,
+              stopped: Arc<AtomicBool>) {
+        for stream in listener.incoming() {
+            if stopped.load(Ordering::SeqCst) {
+                break;
+            }
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    warn!("A cluster connection could not be accepted: {}", error);
+                    continue;
+                },
+            };
+            if let Ok(connection) = stream.try_clone() {
+                connections.lock().unwrap().push(connection);
+            }
+            let handler = handler.clone();
+            let _ = thread::spawn(move || Transport::read(stream, handler));
+        }
+    }
+
+    /// Reads the messages of a connection until it is closed.
+    fn read(mut stream: TcpStream, handler: MessageHandler) {
+        loop {
+            let mut len = [0; 4];
+            if stream.read_exact(&mut len).is_err() {
+                return;
+            }
+            let len = u32::from_bytes(&len).unwrap() as usize;
+            if len > MAX_FRAME_SIZE {
+                warn!("A message of {} bytes was received from another node, the connection is closed", len);
+                let _ = stream.shutdown(Shutdown::Both);
+                return;
+            }
+            let mut payload = vec![0; len];
+            if stream.read_exact(&mut payload).is_err() {
+                return;
+            }
+            match ClusterMessage::from_payload(&payload) {
+                Ok(message) => handler(message),
+                Err(error) => warn!("Received an invalid cluster message: {}", error),
+            }
+        }
+    }
+
+    /// Writes the frames to the node, connecting again when the connection is lost.
+    fn write(address: String, frames: Receiver<Vec<u8>>) {
+        let mut connection: Option<TcpStream> = None;
+        for frame in frames {
+            if connection.is_none() {
+                connection = connect(&address);
+            }
+            let written = match connection {
+                Some(ref mut stream) => stream.write_all(&frame).is_ok(),
+                None => true,
+            };
+            if !written {
+                debug!("The connection to {} was lost", address);
+                connection = None;
+            }
+        }
+    }
+}
+
+impl Drop for Transport {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn connect(address: &str) -> Option<TcpStream> {
+    let address: SocketAddr = match address.to_socket_addrs().ok().and_then(|mut addresses| addresses.next()) {
+        Some(address) => address,
+        None => return None,
+    };
+    let stream = TcpStream::connect_timeout(&address, CONNECTION_TIMEOUT).ok()?;
+    let _ = stream.set_nodelay(true);
+    let _ = stream.set_write_timeout(Some(CONNECTION_TIMEOUT));
+    Some(stream)
+}
+
+fn network_error(error: io::Error) -> RobotsError {
+    RobotsError::Network(error.to_string())
+}
@@ -15,6 +15,13 @@
 
 #[macro_use]
 extern crate log;
+extern crate rand;
 
 /// Actors core.
 pub mod actors;
+
+/// Clusters of actor systems.
+pub mod cluster;
+
+/// Persistence of the state of actors.
+pub mod persistence;
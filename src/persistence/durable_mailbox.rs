@@ -0,0 +1,177 @@
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use actors::{Message, RobotsError};
+use persistence::file_name;
+use persistence::persist::{read_u32, read_u64, Persist};
+
+/// Storage of the messages of actors, so that they survive the process.
+///
+/// The messages are stored by the logical path of the actor before being put in its mailbox, and
+/// acknowledged once the actor handled them. The messages that were not acknowledged are given
+/// back when an actor is started with the same path.
+pub trait MailboxStorage: Send + Sync {
+    /// Stores a message sent to the actor, and gives its id. Messages that are not meant to be
+    /// stored give `None`.
+    fn append(&self, path: &str, message: &(Any + Send)) -> Result<Option<u64>, RobotsError>;
+
+    /// Marks the message with the given id as handled.
+    fn acknowledge(&self, path: &str, id: u64) -> Result<(), RobotsError>;
+
+    /// Gives the messages that were not acknowledged, in the order they were stored.
+    fn recover(&self, path: &str) -> Result<Vec<(u64, Box<Any + Send>)>, RobotsError>;
+}
+
+// Kinds of the records of the logs.
+const APPENDED: u8 = 0;
+const ACKNOWLEDGED: u8 = 1;
+
+/// State of the log of an actor.
+struct Log {
+    // The log, opened for appending.
+    file: File,
+    next_id: u64,
+    // Number of messages that are not acknowledged.
+    pending: usize,
+}
+
+/// MailboxStorage appending the messages of type `M` to a log per actor, in the given directory.
+///
+/// Messages of other types (such as control messages) are not stored. A log is emptied when all
+/// its messages are acknowledged.
+pub struct DurableMailbox<M: Message + Persist> {
+    directory: PathBuf,
+    logs: Mutex<HashMap<String, Log>>,
+    _message: PhantomData<M>,
+}
+
+impl<M: Message + Persist> DurableMailbox<M> {
+    /// Creates a storage in the given directory, which is created if needed.
+    pub fn new<P: AsRef<Path>>(directory: P) -> Result<DurableMailbox<M>, RobotsError> {
+        fs::create_dir_all(directory.as_ref())?;
+        Ok(DurableMailbox {
+            directory: directory.as_ref().to_path_buf(),
+            logs: Mutex::new(HashMap::new()),
+            _message: PhantomData,
+        })
+    }
+
+    fn path(&self, path: &str) -> PathBuf {
+        self.directory.join(format!("{}.mailbox", file_name(path)))
+    }
+
+    /// Opens the log of the actor for appending.
+    fn open(&self, path: &str) -> Result<File, RobotsError> {
+        Ok(OpenOptions::new().create(true).append(true).open(self.path(path))?)
+    }
+
+    /// Gives the records of the log of the actor that were completely written.
+    fn read(&self, path: &str) -> Result<Vec<(u8, u64, Vec<u8>)>, RobotsError> {
+        let mut bytes = Vec::new();
+        match File::open(self.path(path)) {
+            Ok(mut file) => file.read_to_end(&mut bytes)?,
+            Err(ref error) if error.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(RobotsError::from(error)),
+        };
+        let mut records = Vec::new();
+        let mut offset = 0;
+        while let (Some(&kind), Some(id), Some(len)) = (bytes.get(offset), read_u64(&bytes, offset + 1), read_u32(&bytes, offset + 9)) {
+            let start = offset + 13;
+            match bytes.get(start..start + len as usize) {
+                Some(message) => records.push((kind, id, message.to_vec())),
+                None => break,
+            }
+            offset = start + len as usize;
+        }
+        Ok(records)
+    }
+}
+
+impl<M: Message + Persist> MailboxStorage for DurableMailbox<M> {
+    fn append(&self, path: &str, message: &(Any + Send)) -> Result<Option<u64>, RobotsError> {
+        let message = match message.downcast_ref::<M>() {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+        let mut logs = self.logs.lock().unwrap();
+        if !logs.contains_key(path) {
+            let file = self.open(path)?;
+            logs.insert(path.to_owned(), Log {
+                file: file,
+                next_id: 0,
+                pending: 0,
+            });
+        }
+        let log = logs.get_mut(path).unwrap();
+        let id = log.next_id;
+        write(&mut log.file, &record(APPENDED, id, &message.to_bytes()))?;
+        log.next_id += 1;
+        log.pending += 1;
+        Ok(Some(id))
+    }
+
+    fn acknowledge(&self, path: &str, id: u64) -> Result<(), RobotsError> {
+        let mut logs = self.logs.lock().unwrap();
+        let log = match logs.get_mut(path) {
+            Some(log) => log,
+            None => return Ok(()),
+        };
+        log.pending = log.pending.saturating_sub(1);
+        if log.pending == 0 {
+            // Nothing has to be delivered again, the log can be emptied.
+            log.file.set_len(0)?;
+            log.file.sync_data()?;
+        } else {
+            write(&mut log.file, &record(ACKNOWLEDGED, id, &[]))?;
+        }
+        Ok(())
+    }
+
+    fn recover(&self, path: &str) -> Result<Vec<(u64, Box<Any + Send>)>, RobotsError> {
+        let mut logs = self.logs.lock().unwrap();
+        let records = self.read(path)?;
+        let acknowledged: HashSet<u64> = records.iter().filter(|record| record.0 == ACKNOWLEDGED).map(|record| record.1).collect();
+        let mut messages: Vec<(u64, Box<Any + Send>)> = Vec::new();
+        let mut next_id = 0;
+        let mut kept = Vec::new();
+        for (kind, id, bytes) in records {
+            next_id = next_id.max(id + 1);
+            if kind == APPENDED && !acknowledged.contains(&id) {
+                messages.push((id, Box::new(M::from_bytes(&bytes)?)));
+                kept.extend(record(APPENDED, id, &bytes));
+            }
+        }
+        // The log is rewritten with the pending messages only, in another file which then
+        // replaces it.
+        let temporary = self.path(path).with_extension("tmp");
+        write(&mut File::create(&temporary)?, &kept)?;
+        fs::rename(&temporary, self.path(path))?;
+        logs.insert(path.to_owned(), Log {
+            file: self.open(path)?,
+            next_id: next_id,
+            pending: messages.len(),
+        });
+        Ok(messages)
+    }
+}
+
+/// Gives the bytes of a record of a log.
+fn record(kind: u8, id: u64, bytes: &[u8]) -> Vec<u8> {
+    let mut record = vec![kind];
+    record.extend(id.to_bytes());
+    record.extend((bytes.len() as u32).to_bytes());
+    record.extend_from_slice(bytes);
+    record
+}
+
+/// Writes the bytes to the file, and waits for them to be written.
+fn write(file: &mut File, bytes: &[u8]) -> Result<(), RobotsError> {
+    file.write_all(bytes)?;
+    file.sync_data()?;
+    Ok(())
+}
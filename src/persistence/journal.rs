@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use actors::RobotsError;
+use persistence::file_name;
+use persistence::persist::{read_u32, read_u64, Persist};
+
+/// Storage of the events of persistent actors, by persistence id.
+///
+/// The events of an actor are numbered from 1 by the actor, with no gap.
+pub trait Journal: Send + Sync {
+    /// Appends an event of the actor with the given persistence id.
+    ///
+    /// The event must be stored when this returns `Ok`.
+    fn write(&self, persistence_id: &str, sequence_nr: u64, event: &[u8]) -> Result<(), RobotsError>;
+
+    /// Gives the events of the actor with the given persistence id, starting at the given sequence
+    /// number, in order.
+    fn read(&self, persistence_id: &str, from_sequence_nr: u64) -> Result<Vec<(u64, Vec<u8>)>, RobotsError>;
+
+    /// Deletes the events of the actor with the given persistence id up to the given sequence
+    /// number (included).
+    fn delete_to(&self, persistence_id: &str, to_sequence_nr: u64) -> Result<(), RobotsError>;
+}
+
+/// Journal keeping the events in memory, they are thus lost with the process.
+#[derive(Default)]
+pub struct InMemoryJournal {
+    events: Mutex<HashMap<String, Vec<(u64, Vec<u8>)>>>,
+}
+
+impl InMemoryJournal {
+    /// Creates an empty journal.
+    pub fn new() -> InMemoryJournal {
+        InMemoryJournal::default()
+    }
+}
+
+impl Journal for InMemoryJournal {
+    fn write(&self, persistence_id: &str, sequence_nr: u64, event: &[u8]) -> Result<(), RobotsError> {
+        let mut events = self.events.lock().unwrap();
+        events.entry(persistence_id.to_owned()).or_default().push((sequence_nr, event.to_vec()));
+        Ok(())
+    }
+
+    fn read(&self, persistence_id: &str, from_sequence_nr: u64) -> Result<Vec<(u64, Vec<u8>)>, RobotsError> {
+        let events = self.events.lock().unwrap();
+        Ok(events.get(persistence_id)
+                 .map(|events| events.iter().filter(|event| event.0 >= from_sequence_nr).cloned().collect())
+                 .unwrap_or_default())
+    }
+
+    fn delete_to(&self, persistence_id: &str, to_sequence_nr: u64) -> Result<(), RobotsError> {
+        let mut events = self.events.lock().unwrap();
+        if let Some(events) = events.get_mut(persistence_id) {
+            events.retain(|event| event.0 > to_sequence_nr);
+        }
+        Ok(())
+    }
+}
+
+/// Journal appending the events to a file per persistence id, in the given directory.
+///
+/// Each event is written as its sequence number and its length, followed by its bytes. An event
+/// that was not completely written (because the process died while writing it) is removed from the
+/// file when the events are read.
+pub struct FileJournal {
+    directory: PathBuf,
+    // Reads and writes are done one at a time, reads may truncate the files.
+    lock: Mutex<()>,
+}
+
+impl FileJournal {
+    /// Creates a journal in the given directory, which is created if needed.
+    pub fn new<P: AsRef<Path>>(directory: P) -> Result<FileJournal, RobotsError> {
+        fs::create_dir_all(directory.as_ref())?;
+        Ok(FileJournal {
+            directory: directory.as_ref().to_path_buf(),
+            lock: Mutex::new(()),
+        })
+    }
+
+    fn path(&self, persistence_id: &str) -> PathBuf {
+        self.directory.join(format!("{}.journal", file_name(persistence_id)))
+    }
+
+    /// Gives all the events of the file, and removes the incomplete one at its end if any.
+    fn read_file(&self, persistence_id: &str, path: &Path) -> Result<Vec<(u64, Vec<u8>)>, RobotsError> {
+        let mut bytes = Vec::new();
+        match File::open(path) {
+            Ok(mut file) => file.read_to_end(&mut bytes)?,
+            Err(ref error) if error.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(RobotsError::from(error)),
+        };
+        let mut events = Vec::new();
+        let mut offset = 0;
+        while let (Some(sequence_nr), Some(len)) = (read_u64(&bytes, offset), read_u32(&bytes, offset + 8)) {
+            let start = offset + 12;
+            match bytes.get(start..start + len as usize) {
+                Some(event) => events.push((sequence_nr, event.to_vec())),
+                None => break,
+            };
+            offset = start + len as usize;
+        }
+        if offset < bytes.len() {
+            warn!("Removing an incomplete event at the end of the journal of {}", persistence_id);
+            OpenOptions::new().write(true).open(path)?.set_len(offset as u64)?;
+        }
+        Ok(events)
+    }
+}
+
+fn record(sequence_nr: u64, event: &[u8]) -> Vec<u8> {
+    let mut record = sequence_nr.to_bytes();
+    record.extend((event.len() as u32).to_bytes());
+    record.extend_from_slice(event);
+    record
+}
+
+impl Journal for FileJournal {
+    fn write(&self, persistence_id: &str, sequence_nr: u64, event: &[u8]) -> Result<(), RobotsError> {
+        let _lock = self.lock.lock().unwrap();
+        let mut file = OpenOptions::new().create(true).append(true).open(self.path(persistence_id))?;
+        file.write_all(&record(sequence_nr, event))?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    fn read(&self, persistence_id: &str, from_sequence_nr: u64) -> Result<Vec<(u64, Vec<u8>)>, RobotsError> {
+        let _lock = self.lock.lock().unwrap();
+        let mut events = self.read_file(persistence_id, &self.path(persistence_id))?;
+        events.retain(|event| event.0 >= from_sequence_nr);
+        Ok(events)
+    }
+
+    fn delete_to(&self, persistence_id: &str, to_sequence_nr: u64) -> Result<(), RobotsError> {
+        let _lock = self.lock.lock().unwrap();
+        let path = self.path(persistence_id);
+        let mut bytes = Vec::new();
+        for (sequence_nr, event) in self.read_file(persistence_id, &path)? {
+            if sequence_nr > to_sequence_nr {
+                bytes.extend(record(sequence_nr, &event));
+            }
+        }
+        // The kept events are written to another file which replaces the journal, so that the
+        // journal is never partially rewritten.
+        let temporary = path.with_extension("tmp");
+        {
+            let mut file = File::create(&temporary)?;
+            file.write_all(&bytes)?;
+            file.sync_data()?;
+        }
+        fs::rename(&temporary, &path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,26 @@
+pub use self::durable_mailbox::{DurableMailbox, MailboxStorage};
+pub use self::journal::{FileJournal, InMemoryJournal, Journal};
+pub use self::persist::Persist;
+pub use self::persistent_actor::{Persistence, Persistent, PersistentActor};
+pub use self::snapshot::{FileSnapshotStore, Retention, SnapshotStore};
+
+/// Module with the durable mailboxes, keeping the messages of actors in the storage.
+mod durable_mailbox;
+
+/// Module with the Journals, where the events of persistent actors are written.
+mod journal;
+
+/// Module with the serialization of the values written to the storage.
+mod persist;
+
+/// Module with the persistent actors, rebuilding their state from their events.
+mod persistent_actor;
+
+/// Module with the SnapshotStores, where the snapshots of persistent actors are saved.
+mod snapshot;
+
+/// Gives a name usable in a file name for the given persistence id, by writing its bytes in
+/// hexadecimal.
+fn file_name(persistence_id: &str) -> String {
+    persistence_id.bytes().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,92 @@
+use std::mem;
+
+use actors::RobotsError;
+
+/// Values that can be written to the storage, such as the events of a persistent actor.
+///
+/// `from_bytes` must give back the value given to `to_bytes`.
+pub trait Persist: Sized + Send + 'static {
+    /// Serializes the value.
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// Deserializes a value, this fails with `RobotsError::Serialization` if the bytes are not a
+    /// valid value.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, RobotsError>;
+}
+
+impl Persist for () {
+    fn to_bytes(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn from_bytes(_bytes: &[u8]) -> Result<(), RobotsError> {
+        Ok(())
+    }
+}
+
+impl Persist for Vec<u8> {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.clone()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Vec<u8>, RobotsError> {
+        Ok(bytes.to_vec())
+    }
+}
+
+impl Persist for String {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<String, RobotsError> {
+        String::from_utf8(bytes.to_vec()).map_err(|error| RobotsError::Serialization(error.to_string()))
+    }
+}
+
+impl Persist for bool {
+    fn to_bytes(&self) -> Vec<u8> {
+        vec![*self as u8]
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<bool, RobotsError> {
+        match bytes {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(RobotsError::Serialization("invalid boolean".to_owned())),
+        }
+    }
+}
+
+macro_rules! persist_integer {
+    ($($integer:ty),*) => {
+        $(
+            impl Persist for $integer {
+                fn to_bytes(&self) -> Vec<u8> {
+                    self.to_le_bytes().to_vec()
+                }
+
+                fn from_bytes(bytes: &[u8]) -> Result<$integer, RobotsError> {
+                    if bytes.len() != mem::size_of::<$integer>() {
+                        return Err(RobotsError::Serialization(format!("invalid {}", stringify!($integer))));
+                    }
+                    let mut buffer = [0; mem::size_of::<$integer>()];
+                    buffer.copy_from_slice(bytes);
+                    Ok(<$integer>::from_le_bytes(buffer))
+                }
+            }
+        )*
+    }
+}
+
+persist_integer!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// Reads a little endian u32 at the given offset, if there are enough bytes.
+pub(crate) fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    bytes.get(offset..offset + 4).map(|bytes| u32::from_bytes(bytes).unwrap())
+}
+
+/// Reads a little endian u64 at the given offset, if there are enough bytes.
+pub(crate) fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    bytes.get(offset..offset + 8).map(|bytes| u64::from_bytes(bytes).unwrap())
+}
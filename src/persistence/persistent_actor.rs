@@ -0,0 +1,199 @@
+use std::any::Any;
+use std::sync::{Arc, Mutex};
+
+use actors::{Actor, ActorCell, ActorContext, Arguments, Props, RobotsError};
+use actors::props::ActorFactory;
+use persistence::{Journal, Persist, Retention, SnapshotStore};
+
+/// Persistence id, journal, snapshot store and sequence number of a persistent actor.
+pub struct Persistence {
+    persistence_id: String,
+    journal: Arc<Journal>,
+    snapshot_store: Option<(Arc<SnapshotStore>, Retention)>,
+    // Sequence number of the last event written or replayed.
+    sequence_nr: Mutex<u64>,
+}
+
+impl Persistence {
+    /// Creates the Persistence of an actor whose events are written to the journal under the given
+    /// persistence id.
+    ///
+    /// The persistence id must be unique and stable: an actor recovers the events written with its
+    /// persistence id, even by a previous process.
+    pub fn new(persistence_id: String, journal: Arc<Journal>) -> Persistence {
+        Persistence {
+            persistence_id: persistence_id,
+            journal: journal,
+            snapshot_store: None,
+            sequence_nr: Mutex::new(0),
+        }
+    }
+
+    /// Same as `new`, with a store where the actor can save snapshots of its state.
+    ///
+    /// When a snapshot is saved the older snapshots and the events are deleted as given by the
+    /// retention.
+    pub fn with_snapshots(persistence_id: String,
+                          journal: Arc<Journal>,
+                          snapshot_store: Arc<SnapshotStore>,
+                          retention: Retention)
+                          -> Persistence {
+        Persistence {
+            persistence_id: persistence_id,
+            journal: journal,
+            snapshot_store: Some((snapshot_store, retention)),
+            sequence_nr: Mutex::new(0),
+        }
+    }
+
+    /// Persistence id of the actor.
+    pub fn persistence_id(&self) -> &str {
+        &self.persistence_id
+    }
+
+    /// Sequence number of the last event written or replayed, 0 if there is none.
+    pub fn last_sequence_nr(&self) -> u64 {
+        *self.sequence_nr.lock().unwrap()
+    }
+
+    /// Writes an event to the journal, and gives its sequence number.
+    fn write<E: Persist>(&self, event: &E) -> Result<u64, RobotsError> {
+        let mut sequence_nr = self.sequence_nr.lock().unwrap();
+        self.journal.write(&self.persistence_id, *sequence_nr + 1, &event.to_bytes())?;
+        *sequence_nr += 1;
+        Ok(*sequence_nr)
+    }
+
+    /// Saves a snapshot including the events up to the last one, and deletes what the retention
+    /// allows.
+    fn save_snapshot<S: Persist>(&self, snapshot: &S) -> Result<(), RobotsError> {
+        let (snapshot_store, retention) = match self.snapshot_store {
+            Some((ref snapshot_store, retention)) => (snapshot_store, retention),
+            None => return Err(RobotsError::Storage(format!("{} has no snapshot store", self.persistence_id))),
+        };
+        let sequence_nr = self.sequence_nr.lock().unwrap();
+        snapshot_store.save(&self.persistence_id, *sequence_nr, &snapshot.to_bytes())?;
+        if retention.keep_snapshots > 0 {
+            snapshot_store.delete_older(&self.persistence_id, retention.keep_snapshots)?;
+        }
+        if retention.delete_events {
+            self.journal.delete_to(&self.persistence_id, *sequence_nr)?;
+        }
+        Ok(())
+    }
+
+    /// Gives the latest snapshot to the snapshot handler, and then the newer events of the journal
+    /// to the event handler, in order.
+    fn replay<S, E, F, G>(&self, snapshot_handler: F, mut event_handler: G) -> Result<(), RobotsError>
+        where S: Persist,
+              E: Persist,
+              F: FnOnce(S),
+              G: FnMut(E)
+    {
+        let mut sequence_nr = self.sequence_nr.lock().unwrap();
+        if let Some((ref snapshot_store, _)) = self.snapshot_store {
+            if let Some((snapshot_sequence_nr, snapshot)) = snapshot_store.load(&self.persistence_id)? {
+                snapshot_handler(S::from_bytes(&snapshot)?);
+                *sequence_nr = snapshot_sequence_nr;
+            }
+        }
+        for (event_sequence_nr, event) in self.journal.read(&self.persistence_id, *sequence_nr + 1)? {
+            event_handler(E::from_bytes(&event)?);
+            *sequence_nr = event_sequence_nr;
+        }
+        Ok(())
+    }
+}
+
+/// Actor whose state is rebuilt from the events it persisted (event sourcing).
+///
+/// Such an actor changes its state only by persisting events with `persist`, and applying them in
+/// the handler given to `persist`. When it is started (or restarted, or created again with the
+/// same persistence id) the events of its journal are replayed with `receive_recover`, which must
+/// apply them the same way, before it handles any message.
+///
+/// If its Persistence has a snapshot store, the actor can save its whole state with
+/// `save_snapshot`. The recovery then starts with the latest snapshot, given to
+/// `receive_snapshot`, and only replays the newer events.
+///
+/// A PersistentActor is run by a `Persistent` actor, created with `Persistent::props`, which gives
+/// it its messages with `receive_command`.
+pub trait PersistentActor: Send + Sync + 'static {
+    /// Type of the events persisted by the actor.
+    type Event: Persist;
+
+    /// Type of the snapshots of the state of the actor, `()` if it saves none.
+    type Snapshot: Persist;
+
+    /// Gives the Persistence of the actor.
+    fn persistence(&self) -> &Persistence;
+
+    /// Handles a message, this is the equivalent of `Actor::receive`.
+    fn receive_command(&self, command: Box<Any>, context: ActorCell);
+
+    /// Applies an event replayed from the journal.
+    fn receive_recover(&self, event: Self::Event, context: ActorCell);
+
+    /// Restores the state from a snapshot, before the newer events are replayed.
+    fn receive_snapshot(&self, _snapshot: Self::Snapshot, _context: ActorCell) {}
+
+    /// Method called once all the events are replayed.
+    fn recovery_completed(&self, _context: ActorCell) {}
+
+    /// Writes the event to the journal, and then gives it to the handler.
+    ///
+    /// If the write fails the handler is not called and the actor fails with the error.
+    fn persist<F: FnOnce(Self::Event)>(&self, event: Self::Event, context: ActorCell, handler: F)
+        where Self: Sized
+    {
+        match self.persistence().write(&event) {
+            Ok(_) => handler(event),
+            Err(error) => context.fail(error),
+        }
+    }
+
+    /// Saves a snapshot of the state of the actor, which must include all the events persisted so
+    /// far.
+    ///
+    /// This fails if the Persistence of the actor has no snapshot store, or if the snapshot could
+    /// not be saved, the actor can then keep going with its events.
+    fn save_snapshot(&self, snapshot: &Self::Snapshot) -> Result<(), RobotsError>
+        where Self: Sized
+    {
+        self.persistence().save_snapshot(snapshot)
+    }
+}
+
+/// Actor running a PersistentActor: it replays the events of the PersistentActor when it starts,
+/// and then gives it its messages.
+pub struct Persistent<A: PersistentActor> {
+    actor: A,
+}
+
+impl<A: PersistentActor> Persistent<A> {
+    /// Gives Props for a Persistent actor running the PersistentActor created with the `creator`
+    /// function and `args` args.
+    pub fn props<Args: Arguments>(creator: Arc<Fn(Args) -> A + Sync + Send>, args: Args) -> Arc<ActorFactory> {
+        Props::new(Arc::new(move |args| Persistent { actor: creator(args) }), args)
+    }
+}
+
+impl<A: PersistentActor> Actor for Persistent<A> {
+    fn receive(&self, message: Box<Any>, context: ActorCell) {
+        self.actor.receive_command(message, context);
+    }
+
+    fn pre_start(&self, context: ActorCell) {
+        let actor = &self.actor;
+        let replayed = actor.persistence().replay(|snapshot| actor.receive_snapshot(snapshot, context.clone()),
+                                                  |event| actor.receive_recover(event, context.clone()));
+        match replayed {
+            Ok(()) => actor.recovery_completed(context),
+            Err(error) => {
+                // Failing would restart the actor, which would fail to recover again.
+                error!("The recovery of {} failed, it is stopped: {}", actor.persistence().persistence_id(), error);
+                context.kill_me();
+            },
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use actors::RobotsError;
+use persistence::file_name;
+
+/// Storage of the snapshots of the state of persistent actors, by persistence id.
+///
+/// A snapshot is saved with the sequence number of the last event it includes.
+pub trait SnapshotStore: Send + Sync {
+    /// Saves a snapshot of the actor with the given persistence id.
+    ///
+    /// The snapshot must be stored when this returns `Ok`.
+    fn save(&self, persistence_id: &str, sequence_nr: u64, snapshot: &[u8]) -> Result<(), RobotsError>;
+
+    /// Gives the latest snapshot of the actor with the given persistence id, with its sequence
+    /// number.
+    fn load(&self, persistence_id: &str) -> Result<Option<(u64, Vec<u8>)>, RobotsError>;
+
+    /// Deletes the snapshots of the actor with the given persistence id, except the `keep` latest
+    /// ones.
+    fn delete_older(&self, persistence_id: &str, keep: usize) -> Result<(), RobotsError>;
+}
+
+/// What is deleted when a persistent actor saves a snapshot.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Retention {
+    /// Number of snapshots kept, older ones are deleted. 0 keeps them all.
+    pub keep_snapshots: usize,
+    /// Whether the events included in the snapshot are deleted from the journal.
+    pub delete_events: bool,
+}
+
+impl Retention {
+    /// Creates a Retention keeping the given number of snapshots (all of them if 0), and deleting
+    /// the events they include if `delete_events`.
+    pub fn new(keep_snapshots: usize, delete_events: bool) -> Retention {
+        Retention {
+            keep_snapshots: keep_snapshots,
+            delete_events: delete_events,
+        }
+    }
+}
+
+/// SnapshotStore writing each snapshot to a file, in the given directory.
+///
+/// Snapshots are written to a temporary file which is then renamed, so a snapshot is either
+/// complete or not there.
+pub struct FileSnapshotStore {
+    directory: PathBuf,
+    // Operations are done one at a time.
+    lock: Mutex<()>,
+}
+
+impl FileSnapshotStore {
+    /// Creates a store in the given directory, which is created if needed.
+    pub fn new<P: AsRef<Path>>(directory: P) -> Result<FileSnapshotStore, RobotsError> {
+        fs::create_dir_all(directory.as_ref())?;
+        Ok(FileSnapshotStore {
+            directory: directory.as_ref().to_path_buf(),
+            lock: Mutex::new(()),
+        })
+    }
+
+    fn path(&self, persistence_id: &str, sequence_nr: u64) -> PathBuf {
+        self.directory.join(format!("{}.{:020}.snapshot", file_name(persistence_id), sequence_nr))
+    }
+
+    /// Gives the sequence numbers of the snapshots of the actor, in increasing order.
+    fn sequence_nrs(&self, persistence_id: &str) -> Result<Vec<u64>, RobotsError> {
+        let prefix = format!("{}.", file_name(persistence_id));
+        let mut sequence_nrs = Vec::new();
+        for entry in fs::read_dir(&self.directory)? {
+            let name = entry?.file_name();
+            let name = match name.to_str() {
+                Some(name) => name,
+                None => continue,
+            };
+            if name.starts_with(&prefix) && name.ends_with(".snapshot") {
+                if let Ok(sequence_nr) = name[prefix.len()..name.len() - ".snapshot".len()].parse() {
+                    sequence_nrs.push(sequence_nr);
+                }
+            }
+        }
+        sequence_nrs.sort();
+        Ok(sequence_nrs)
+    }
+}
+
+impl SnapshotStore for FileSnapshotStore {
+    fn save(&self, persistence_id: &str, sequence_nr: u64, snapshot: &[u8]) -> Result<(), RobotsError> {
+        let _lock = self.lock.lock().unwrap();
+        let path = self.path(persistence_id, sequence_nr);
+        let temporary = path.with_extension("tmp");
+        {
+            let mut file = File::create(&temporary)?;
+            file.write_all(snapshot)?;
+            file.sync_data()?;
+        }
+        fs::rename(&temporary, &path)?;
+        Ok(())
+    }
+
+    fn load(&self, persistence_id: &str) -> Result<Option<(u64, Vec<u8>)>, RobotsError> {
+        let _lock = self.lock.lock().unwrap();
+        let sequence_nr = match self.sequence_nrs(persistence_id)?.pop() {
+            Some(sequence_nr) => sequence_nr,
+            None => return Ok(None),
+        };
+        let mut snapshot = Vec::new();
+        match File::open(self.path(persistence_id, sequence_nr)) {
+            Ok(mut file) => file.read_to_end(&mut snapshot)?,
+            Err(ref error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(RobotsError::from(error)),
+        };
+        Ok(Some((sequence_nr, snapshot)))
+    }
+
+    fn delete_older(&self, persistence_id: &str, keep: usize) -> Result<(), RobotsError> {
+        let _lock = self.lock.lock().unwrap();
+        let sequence_nrs = self.sequence_nrs(persistence_id)?;
+        let deleted = sequence_nrs.len().saturating_sub(keep);
+        for sequence_nr in &sequence_nrs[..deleted] {
+            fs::remove_file(self.path(persistence_id, *sequence_nr))?;
+        }
+        Ok(())
+    }
+}
@@ -1,12 +1,28 @@
 extern crate env_logger;
 extern crate robots;
 
-use std::any::Any;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::future::Future;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::process;
 use std::sync::{Arc, Mutex};
-use std::sync::mpsc::{channel, Sender};
-use std::time::Duration;
-
-use robots::actors::{Actor, ActorSystem, ActorCell, ActorContext, ActorRef, Props};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+use robots::actors::{Actor, ActorSystem, ActorCell, ActorContext, ActorFailed, ActorPath, ActorRef, ActorStarted,
+                     ActorStopped, AtLeastOnceDelivery, Broadcast, DeadLetter, Delivery, FutureFailure, Props, Resizer,
+                     ResolveRequest, RobotsError, Router, RouterMessage, RoutingStrategy, SystemEvent};
+use robots::cluster::{Cluster, ClusterEvent, ClusterSettings, MemberLeft, MemberRemoved, MemberStatus, MemberUp,
+                      ClusterSingletonManager, ClusterSingletonProxy, PhiAccrualFailureDetector, ShardRegion,
+                      ShardRegionMessage, ShardingSettings, UnreachableMember};
+use robots::persistence::{DurableMailbox, FileJournal, FileSnapshotStore, InMemoryJournal, Journal, MailboxStorage,
+                          Persist, Persistence, Persistent, PersistentActor, Retention, SnapshotStore};
 
 #[derive(Debug, PartialEq)]
 enum Res {
@@ -74,8 +90,8 @@ fn read_messages_in_order() {
     let tx = Arc::new(Mutex::new(tx));
 
     let props = Props::new(Arc::new(InternalState::new), tx);
-    let actor_ref_1 = actor_system.actor_of(props.clone(), "sender".to_owned());
-    let actor_ref_2 = actor_system.actor_of(props.clone(), "receiver".to_owned());
+    let actor_ref_1 = actor_system.actor_of(props.clone(), "sender".to_owned()).unwrap();
+    let actor_ref_2 = actor_system.actor_of(props.clone(), "receiver".to_owned()).unwrap();
 
     for i in 1..1001 {
         actor_ref_1.tell_to(actor_ref_2.clone(), InternalStateMessage::Set(i as u32));
@@ -95,335 +111,2030 @@ fn recover_from_panic() {
     let tx = Arc::new(Mutex::new(tx));
 
     let props = Props::new(Arc::new(InternalState::new), tx);
-    let requester = actor_system.actor_of(props.clone(), "sender".to_owned());
-    let answerer = actor_system.actor_of(props.clone(), "receiver".to_owned());
+    let requester = actor_system.actor_of(props.clone(), "sender".to_owned()).unwrap();
+    let answerer = actor_system.actor_of(props.clone(), "receiver".to_owned()).unwrap();
 
     requester.tell_to(answerer.clone(), InternalStateMessage::Set(10));
-    let res = actor_system.ask(answerer.clone(), InternalStateMessage::Get, "future_1".to_owned());
+    let res = actor_system.ask_anonymous(answerer.clone(), InternalStateMessage::Get);
     let res: u32 = actor_system.extract_result(res);
     assert_eq!(10u32, res);
 
     requester.tell_to(answerer.clone(), InternalStateMessage::Panic);
-    let res = actor_system.ask(answerer, InternalStateMessage::Get, "future_2".to_owned());
+    let res = actor_system.ask_anonymous(answerer, InternalStateMessage::Get);
     let res: u32 = actor_system.extract_result(res);
     assert_eq!(0u32, res);
 
     actor_system.shutdown();
 }
 
-////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
-
-struct Resolver;
+struct Silent;
 
-impl Actor for Resolver {
-    fn receive(&self, message: Box<Any>, context: ActorCell) {
-        if let Ok(message) = Box::<Any>::downcast::<String>(message) {
-            let future = context.identify_actor(*message, "resolver_request".to_owned());
-            context.forward_result_to_future::<Option<ActorRef>>(future, context.sender());
-        }
-    }
+impl Actor for Silent {
+    fn receive(&self, _message: Box<Any>, _context: ActorCell) {}
 }
 
-impl Resolver {
-    fn new(_dummy: ()) -> Resolver {
-        Resolver
+impl Silent {
+    fn new(_dummy: ()) -> Silent {
+        Silent
     }
 }
 
 #[test]
-fn resolve_name_real_path() {
+fn ask_timeout_with_answer() {
     let actor_system = ActorSystem::new("test".to_owned());
 
-    let props = Props::new(Arc::new(Resolver::new), ());
-    let answerer = actor_system.actor_of(props.clone(), "answerer".to_owned());
-    let requester = actor_system.actor_of(props.clone(), "sender".to_owned());
+    let (tx, _rx) = channel();
+    let tx = Arc::new(Mutex::new(tx));
 
-    // We wait to be sure that the actors will be registered to the name resolver.
-    std::thread::sleep(Duration::from_millis(100));
+    let props = Props::new(Arc::new(InternalState::new), tx);
+    let answerer = actor_system.actor_of(props, "answerer".to_owned()).unwrap();
 
-    let res = actor_system.ask(answerer, "/user/sender".to_owned(), "future".to_owned());
-    let res: Option<ActorRef> = actor_system.extract_result(res);
-    assert_eq!(requester.path(), res.unwrap().path());
+    let res = actor_system.ask_timeout(answerer, InternalStateMessage::Get, Duration::from_secs(5));
+    let res: Result<u32, RobotsError> = actor_system.extract_result_timeout(res, Duration::from_secs(5));
+    assert_eq!(Ok(0u32), res);
 
     actor_system.shutdown();
 }
 
 #[test]
-fn resolve_name_fake_path() {
+fn ask_timeout_without_answer() {
     let actor_system = ActorSystem::new("test".to_owned());
 
-    let props = Props::new(Arc::new(Resolver::new), ());
-    let answerer = actor_system.actor_of(props.clone(), "answerer".to_owned());
+    let props = Props::new(Arc::new(Silent::new), ());
+    let silent = actor_system.actor_of(props, "silent".to_owned()).unwrap();
 
-    // We wait to be sure that the actors will be registered to the name resolver.
-    std::thread::sleep(Duration::from_millis(100));
+    let res = actor_system.ask_timeout(silent, (), Duration::from_millis(50));
+    let res: Result<u32, RobotsError> = actor_system.extract_result_timeout(res, Duration::from_secs(5));
+    assert_eq!(Err(RobotsError::Timeout), res);
 
-    let res = actor_system.ask(answerer, "/foo/bar".to_owned(), "future".to_owned());
-    let res: Option<ActorRef> = actor_system.extract_result(res);
+    actor_system.shutdown();
+}
 
-    match res {
-        None => {}
-        Some(_) => panic!("The name resolver gave an ActorRef when he should not."),
-    };
+#[test]
+fn schedule_once_after_shutdown() {
+    let actor_system = ActorSystem::new("test".to_owned());
 
+    let props = Props::new(Arc::new(Silent::new), ());
+    let silent = actor_system.actor_of(props, "silent".to_owned()).unwrap();
     actor_system.shutdown();
+
+    // The message is dropped.
+    actor_system.schedule_once(Duration::from_millis(10), silent, ());
 }
 
-////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+#[test]
+fn extract_result_timeout_without_answer() {
+    let actor_system = ActorSystem::new("test".to_owned());
 
-struct SimpleActor1 {
-    sender: Arc<Mutex<Sender<Res>>>,
-}
+    let props = Props::new(Arc::new(Silent::new), ());
+    let silent = actor_system.actor_of(props, "silent".to_owned()).unwrap();
 
-impl Actor for SimpleActor1 {
-    fn receive(&self, _message: Box<Any>, _context: ActorCell) {
-        let _ = self.sender.lock().unwrap().send(Res::Ok);
-    }
-}
+    let res = actor_system.ask_anonymous(silent, ());
+    let res: Result<u32, RobotsError> = actor_system.extract_result_timeout(res, Duration::from_millis(50));
+    assert_eq!(Err(RobotsError::Timeout), res);
 
-impl SimpleActor1 {
-    fn new(sender: Arc<Mutex<Sender<Res>>>) -> SimpleActor1 {
-        SimpleActor1 {
-            sender: sender,
-        }
-    }
+    actor_system.shutdown();
 }
 
 #[test]
-fn receive_message () {
-    env_logger::init().unwrap();
+fn extract_result_of_the_wrong_type() {
     let actor_system = ActorSystem::new("test".to_owned());
 
-    let (tx, rx) = channel();
+    let (tx, _rx) = channel();
     let tx = Arc::new(Mutex::new(tx));
 
-    let props = Props::new(Arc::new(SimpleActor1::new), tx);
-    let actor_ref = actor_system.actor_of(props.clone(), "actor".to_owned());
-
-    actor_system.tell(actor_ref, ());
+    let props = Props::new(Arc::new(InternalState::new), tx);
+    let answerer = actor_system.actor_of(props, "answerer".to_owned()).unwrap();
 
-    let res = rx.recv();
-    assert_eq!(Ok(Res::Ok), res);
+    // The answerer completes the future with a u32.
+    let res = actor_system.ask_anonymous(answerer, InternalStateMessage::Get);
+    let res: Result<String, RobotsError> = actor_system.extract_result_timeout(res, Duration::from_secs(5));
+    assert_eq!(Err(RobotsError::FutureFailed(FutureFailure::WrongType)), res);
 
     actor_system.shutdown();
 }
 
-////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
-
-struct SimpleActor2 {
-    sender: Arc<Mutex<Sender<Res>>>,
-    state: i32,
-}
+struct PanickingComputation;
 
-impl Actor for SimpleActor2 {
-    fn receive(&self, message: Box<Any>, _context: ActorCell) {
-        if let Ok(message) = Box::<Any>::downcast::<i32>(message) {
-            if *message == self.state {
-                let _ = self.sender.lock().unwrap().send(Res::Ok);
-            } else {
-                let _ = self.sender.lock().unwrap().send(Res::Err);
-            }
+impl Actor for PanickingComputation {
+    fn receive(&self, message: Box<Any>, context: ActorCell) {
+        if let Ok(message) = Box::<Any>::downcast::<ActorRef>(message) {
+            let future = context.ask_anonymous(*message, InternalStateMessage::Get);
+            context.do_computation(future.clone(), |_value, _context| -> u32 {
+                panic!("The computation panicked as planned.")
+            });
+            // This computation is skipped since the future has failed.
+            context.do_computation(future.clone(), |_value, _context| 42u32);
+            context.forward_result_to_future::<u32>(future, context.sender());
         }
     }
 }
 
-impl SimpleActor2 {
-    fn new(args: (Arc<Mutex<Sender<Res>>>, i32)) -> SimpleActor2 {
-        SimpleActor2 {
-            sender: args.0,
-            state: args.1,
-        }
+impl PanickingComputation {
+    fn new(_dummy: ()) -> PanickingComputation {
+        PanickingComputation
     }
 }
 
 #[test]
-fn receive_correct_message () {
+fn propagate_computation_failure() {
     let actor_system = ActorSystem::new("test".to_owned());
 
-    let (tx, rx) = channel();
+    let (tx, _rx) = channel();
     let tx = Arc::new(Mutex::new(tx));
-    let value = 42;
-
-    let props = Props::new(Arc::new(SimpleActor2::new), (tx, value));
-    let actor_ref = actor_system.actor_of(props.clone(), "actor".to_owned());
 
-    actor_system.tell(actor_ref, value);
+    let answerer = actor_system.actor_of(Props::new(Arc::new(InternalState::new), tx), "answerer".to_owned()).unwrap();
+    let computer = actor_system.actor_of(Props::new(Arc::new(PanickingComputation::new), ()), "computer".to_owned()).unwrap();
 
-    let res = rx.recv();
-    assert_eq!(Ok(Res::Ok), res);
+    let res = actor_system.ask_anonymous(computer, answerer);
+    let res: Result<u32, RobotsError> = actor_system.extract_result_timeout(res, Duration::from_secs(5));
+    assert_eq!(Err(RobotsError::FutureFailed(FutureFailure::Panicked)), res);
 
     actor_system.shutdown();
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
 
-struct SimpleActor3;
+struct ThreadWaker(Thread);
 
-impl Actor for SimpleActor3 {
-    fn receive(&self, _message: Box<Any>, _context: ActorCell) {
-        panic!("Panic as planned, should start an failure handler.");
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
     }
 }
 
-impl SimpleActor3 {
-    fn new(_args: ()) -> SimpleActor3 {
-        SimpleActor3
+/// Minimal executor, polls the future on the current thread and parks it until it is woken up.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut context = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
     }
 }
 
-#[derive(Clone)]
-enum SimpleActor4Messages {
-    RegisterMe,
-    ActorFailure,
+#[test]
+fn await_future() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let mut actors = internal_states(&actor_system, vec![3]);
+
+    let res = actor_system.ask_anonymous(actors.pop().unwrap(), InternalStateMessage::Get);
+    let res = block_on(actor_system.async_result::<u32>(res));
+    assert_eq!(Ok(3), res);
+
+    actor_system.shutdown();
 }
 
-struct SimpleActor4 {
-    sender: Arc<Mutex<Sender<Res>>>,
+#[test]
+fn await_failed_future() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let props = Props::new(Arc::new(Silent::new), ());
+    let silent = actor_system.actor_of(props, "silent".to_owned()).unwrap();
+
+    let res = actor_system.ask_timeout(silent, (), Duration::from_millis(50));
+    let res = block_on(actor_system.async_result::<u32>(res));
+    assert_eq!(Err(RobotsError::Timeout), res);
+
+    actor_system.shutdown();
 }
 
-impl Actor for SimpleActor4 {
+#[derive(Clone)]
+enum CombinatorMessage {
+    Map(ActorRef),
+    AndThen(ActorRef, ActorRef),
+    Zip(ActorRef, ActorRef),
+    Sequence(Vec<ActorRef>),
+    FirstCompleted(Vec<ActorRef>),
+}
+
+/// Asks the given InternalState actors for their value and combines the Futures.
+struct Combinator;
+
+impl Actor for Combinator {
     fn receive(&self, message: Box<Any>, context: ActorCell) {
-        if let Ok(message) = Box::<Any>::downcast::<SimpleActor4Messages>(message) {
+        if let Ok(message) = Box::<Any>::downcast::<CombinatorMessage>(message) {
             match *message {
-                SimpleActor4Messages::RegisterMe => {
-                    context.monitor(context.sender(), Arc::new(|_failure, context| {
-                        // When the monitored actor failed, the monitorer pipes a message to itself.
-                        context.tell(context.actor_ref(), SimpleActor4Messages::ActorFailure);
-                    }));
-                    context.tell(context.sender(), ());
+                CombinatorMessage::Map(actor) => {
+                    let future = context.ask_anonymous(actor, InternalStateMessage::Get);
+                    let future = context.map(future, |value: u32| format!("value: {}", value));
+                    context.forward_result_to_future::<String>(future, context.sender());
+                },
+                CombinatorMessage::AndThen(first, second) => {
+                    let future = context.ask_anonymous(first, InternalStateMessage::Get);
+                    let future = context.and_then(future, move |_value: u32, context| {
+                        context.ask_anonymous(second.clone(), InternalStateMessage::Get)
+                    });
+                    context.forward_result_to_future::<u32>(future, context.sender());
+                },
+                CombinatorMessage::Zip(first, second) => {
+                    let first = context.ask_anonymous(first, InternalStateMessage::Get);
+                    let second = context.ask_anonymous(second, InternalStateMessage::Get);
+                    let future = context.zip::<u32, u32>(first, second);
+                    context.forward_result_to_future::<(u32, u32)>(future, context.sender());
+                },
+                CombinatorMessage::Sequence(actors) => {
+                    let futures = actors.into_iter().map(|actor| {
+                        context.ask_anonymous(actor, InternalStateMessage::Get)
+                    }).collect();
+                    let future = context.sequence::<u32>(futures);
+                    context.forward_result_to_future::<Vec<u32>>(future, context.sender());
+                },
+                CombinatorMessage::FirstCompleted(actors) => {
+                    let futures = actors.into_iter().map(|actor| {
+                        context.ask_anonymous(actor, InternalStateMessage::Get)
+                    }).collect();
+                    let future = context.first_completed(futures);
+                    context.forward_result_to_future::<u32>(future, context.sender());
                 },
-                SimpleActor4Messages::ActorFailure => {
-                    let _ = self.sender.lock().unwrap().send(Res::Ok);
-                }
             }
         }
     }
 }
 
-impl SimpleActor4 {
-    fn new(args: Arc<Mutex<Sender<Res>>>) -> SimpleActor4 {
-        SimpleActor4 {
-            sender: args
-        }
+impl Combinator {
+    fn new(_dummy: ()) -> Combinator {
+        Combinator
     }
 }
 
+/// Creates InternalState actors with the given values.
+fn internal_states(actor_system: &ActorSystem, values: Vec<u32>) -> Vec<ActorRef> {
+    let (tx, _rx) = channel();
+    let props = Props::new(Arc::new(InternalState::new), Arc::new(Mutex::new(tx)));
+    values.into_iter().enumerate().map(|(i, value)| {
+        let actor = actor_system.actor_of(props.clone(), format!("state_{}", i)).unwrap();
+        actor_system.tell(actor.clone(), InternalStateMessage::Set(value));
+        actor
+    }).collect()
+}
+
 #[test]
-fn receive_failure_notifications () {
+fn map_future() {
     let actor_system = ActorSystem::new("test".to_owned());
 
-    let (tx, rx) = channel();
-    let tx = Arc::new(Mutex::new(tx));
+    let mut actors = internal_states(&actor_system, vec![7]);
+    let combinator = actor_system.actor_of(Props::new(Arc::new(Combinator::new), ()), "combinator".to_owned()).unwrap();
 
-    let props_panicker = Props::new(Arc::new(SimpleActor3::new), ());
-    let props_handler = Props::new(Arc::new(SimpleActor4::new), tx);
-    let panicker = actor_system.actor_of(props_panicker.clone(), "panicker".to_owned());
-    let handler = actor_system.actor_of(props_handler.clone(), "handler".to_owned());
+    let res = actor_system.ask_anonymous(combinator, CombinatorMessage::Map(actors.pop().unwrap()));
+    let res: String = actor_system.extract_result(res);
+    assert_eq!("value: 7", res);
 
-    panicker.tell_to(handler.clone(), SimpleActor4Messages::RegisterMe);
+    actor_system.shutdown();
+}
 
-    let res = rx.recv();
-    assert_eq!(Ok(Res::Ok), res);
+#[test]
+fn and_then_future() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let mut actors = internal_states(&actor_system, vec![1, 2]);
+    let combinator = actor_system.actor_of(Props::new(Arc::new(Combinator::new), ()), "combinator".to_owned()).unwrap();
+
+    let second = actors.pop().unwrap();
+    let first = actors.pop().unwrap();
+    let res = actor_system.ask_anonymous(combinator, CombinatorMessage::AndThen(first, second));
+    let res: u32 = actor_system.extract_result(res);
+    assert_eq!(2, res);
 
     actor_system.shutdown();
 }
 
-////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
-struct SimpleActor5;
+#[test]
+fn zip_futures() {
+    let actor_system = ActorSystem::new("test".to_owned());
 
-impl Actor for SimpleActor5 {
-    fn receive(&self, _message: Box<Any>, context: ActorCell) {
-        context.fail("failure");
-    }
-}
+    let mut actors = internal_states(&actor_system, vec![1, 2]);
+    let combinator = actor_system.actor_of(Props::new(Arc::new(Combinator::new), ()), "combinator".to_owned()).unwrap();
 
-impl SimpleActor5 {
-    fn new(_args: ()) -> SimpleActor5 {
-        SimpleActor5
-    }
-}
+    let second = actors.pop().unwrap();
+    let first = actors.pop().unwrap();
+    let res = actor_system.ask_anonymous(combinator, CombinatorMessage::Zip(first, second));
+    let res: (u32, u32) = actor_system.extract_result(res);
+    assert_eq!((1, 2), res);
 
-#[derive(Clone)]
-enum SimpleActor6Messages {
-    RegisterMe,
-    ActorFailureOk,
-    ActorFailureErr,
+    actor_system.shutdown();
 }
 
-struct SimpleActor6 {
-    sender: Arc<Mutex<Sender<Res>>>,
-}
+#[test]
+fn sequence_futures() {
+    let actor_system = ActorSystem::new("test".to_owned());
 
-impl Actor for SimpleActor6 {
-    fn receive(&self, message: Box<Any>, context: ActorCell) {
-        if let Ok(message) = Box::<Any>::downcast::<SimpleActor6Messages>(message) {
-            match *message {
-                SimpleActor6Messages::RegisterMe => {
-                    context.monitor(context.sender(), Arc::new(|failure, context| {
-                        // When the monitored actor failed, the monitorer pipes a message to itself.
-                        match failure.reason() {
-                            "failure" => context.tell(context.actor_ref(), SimpleActor6Messages::ActorFailureOk),
-                            _ => context.tell(context.actor_ref(), SimpleActor6Messages::ActorFailureErr),
-                        };
-                    }));
-                    context.tell(context.sender(), ());
-                },
-                SimpleActor6Messages::ActorFailureOk => {
-                    let _ = self.sender.lock().unwrap().send(Res::Ok);
-                },
-                SimpleActor6Messages::ActorFailureErr => {
-                    let _ = self.sender.lock().unwrap().send(Res::Err);
-                },
-            }
-        }
-    }
+    let actors = internal_states(&actor_system, vec![1, 2, 3, 4]);
+    let combinator = actor_system.actor_of(Props::new(Arc::new(Combinator::new), ()), "combinator".to_owned()).unwrap();
+
+    let res = actor_system.ask_anonymous(combinator, CombinatorMessage::Sequence(actors));
+    let res: Vec<u32> = actor_system.extract_result(res);
+    assert_eq!(vec![1, 2, 3, 4], res);
+
+    actor_system.shutdown();
 }
 
-impl SimpleActor6 {
-    fn new(args: Arc<Mutex<Sender<Res>>>) -> SimpleActor6 {
-        SimpleActor6 {
-            sender: args
-        }
-    }
+#[test]
+fn first_completed_future() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let mut actors = internal_states(&actor_system, vec![5]);
+    let silent = actor_system.actor_of(Props::new(Arc::new(Silent::new), ()), "silent".to_owned()).unwrap();
+    actors.insert(0, silent);
+    let combinator = actor_system.actor_of(Props::new(Arc::new(Combinator::new), ()), "combinator".to_owned()).unwrap();
+
+    let res = actor_system.ask_anonymous(combinator, CombinatorMessage::FirstCompleted(actors));
+    let res: u32 = actor_system.extract_result(res);
+    assert_eq!(5, res);
+
+    actor_system.shutdown();
 }
 
 #[test]
-fn receive_failure_reason_ok () {
+fn concurrent_asks() {
     let actor_system = ActorSystem::new("test".to_owned());
 
-    let (tx, rx) = channel();
-    let tx = Arc::new(Mutex::new(tx));
+    let actors = internal_states(&actor_system, (1..21).collect());
 
-    let props_panicker = Props::new(Arc::new(SimpleActor5::new), ());
-    let props_handler = Props::new(Arc::new(SimpleActor6::new), tx);
-    let panicker = actor_system.actor_of(props_panicker.clone(), "panicker".to_owned());
-    let handler = actor_system.actor_of(props_handler.clone(), "handler".to_owned());
+    // Each ask is made from a thread of its own.
+    let asks: Vec<thread::JoinHandle<u32>> = actors.into_iter().map(|actor| {
+        let actor_system = actor_system.clone();
+        thread::spawn(move || {
+            let future = actor_system.ask_anonymous(actor, InternalStateMessage::Get);
+            actor_system.extract_result(future)
+        })
+    }).collect();
+    let res: Vec<u32> = asks.into_iter().map(|ask| ask.join().unwrap()).collect();
+    assert_eq!((1..21).collect::<Vec<u32>>(), res);
 
-    panicker.tell_to(handler.clone(), SimpleActor6Messages::RegisterMe);
+    actor_system.shutdown();
+}
 
-    let res = rx.recv();
-    assert_eq!(Ok(Res::Ok), res);
+#[test]
+fn anonymous_actors() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let props = Props::new(Arc::new(Silent::new), ());
+    let first = actor_system.actor_of_anonymous(props.clone());
+    let second = actor_system.actor_of_anonymous(props);
 
+    assert_eq!("/user/$a", *first.path().logical_path());
+    assert_eq!("/user/$b", *second.path().logical_path());
+
+    // Once the system is shut down the actor can't be created, its messages are dead letters.
     actor_system.shutdown();
+    let props = Props::new(Arc::new(Silent::new), ());
+    let third = actor_system.actor_of_anonymous(props);
+    assert_eq!("/deadLetters", *third.path().logical_path());
 }
 
 #[test]
-fn receive_failure_reason_err () {
+#[allow(deprecated)]
+fn named_futures() {
     let actor_system = ActorSystem::new("test".to_owned());
 
-    let (tx, rx) = channel();
-    let tx = Arc::new(Mutex::new(tx));
+    let props = Props::new(Arc::new(Resolver::new), ());
+    let resolver = actor_system.actor_of(props, "resolver".to_owned()).unwrap();
 
-    // This actors does not fail with the "failure" reason, so the reason will not be correct
-    let props_panicker = Props::new(Arc::new(SimpleActor3::new), ());
-    let props_handler = Props::new(Arc::new(SimpleActor6::new), tx);
-    let panicker = actor_system.actor_of(props_panicker.clone(), "panicker".to_owned());
-    let handler = actor_system.actor_of(props_handler.clone(), "handler".to_owned());
+    let future = actor_system.ask(resolver.clone(), ("/user/resolver".to_owned(), "request".to_owned()), "future".to_owned());
+    assert_eq!("/user/future", *future.path().logical_path());
+    let resolved: Option<ActorRef> = actor_system.extract_result(future);
+    assert_eq!(resolver.path(), resolved.unwrap().path());
 
-    panicker.tell_to(handler.clone(), SimpleActor6Messages::RegisterMe);
+    // The name of a Future can't be taken twice.
+    let _future = actor_system.ask(resolver.clone(), "/user/resolver".to_owned(), "taken".to_owned());
+    let taken = actor_system.ask(resolver, "/user/resolver".to_owned(), "taken".to_owned());
+    assert_eq!("/deadLetters", *taken.path().logical_path());
 
-    let res = rx.recv();
-    assert_eq!(Ok(Res::Err), res);
+    actor_system.shutdown();
+}
+
+#[test]
+fn duplicate_names() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let props = Props::new(Arc::new(Silent::new), ());
+    let _first = actor_system.actor_of(props.clone(), "silent".to_owned()).unwrap();
+
+    assert_eq!(Err(RobotsError::NameTaken("/user/silent".to_owned())),
+               actor_system.actor_of(props.clone(), "silent".to_owned()).map(|actor| actor.path()));
+    assert_eq!(Err(RobotsError::InvalidName("$silent".to_owned())),
+               actor_system.actor_of(props, "$silent".to_owned()).map(|actor| actor.path()));
 
     actor_system.shutdown();
 }
+
+#[test]
+fn actor_of_after_shutdown() {
+    let actor_system = ActorSystem::new("test".to_owned());
+    actor_system.shutdown();
+
+    let props = Props::new(Arc::new(Silent::new), ());
+    assert_eq!(Err(RobotsError::SystemShutdown),
+               actor_system.actor_of(props, "silent".to_owned()).map(|actor| actor.path()));
+}
+
+struct Stoppable;
+
+impl Actor for Stoppable {
+    fn receive(&self, _message: Box<Any>, context: ActorCell) {
+        context.kill_me();
+    }
+}
+
+impl Stoppable {
+    fn new(_dummy: ()) -> Stoppable {
+        Stoppable
+    }
+}
+
+#[test]
+fn reuse_name_after_termination() {
+    let actor_system = ActorSystem::new("test".to_owned());
+    actor_system.spawn_threads(1);
+    let stops = listen(&actor_system, vec![TypeId::of::<ActorStopped>()]);
+
+    let props = Props::new(Arc::new(Stoppable::new), ());
+    let first = actor_system.actor_of(props.clone(), "stoppable".to_owned()).unwrap();
+    actor_system.tell(first.clone(), ());
+
+    // The actor is dropped once its father removed it from its children, its name is then free.
+    wait_for_event(&stops, "stopped /user/stoppable");
+    let second = actor_system.actor_of(props, "stoppable".to_owned());
+    assert_eq!("/user/stoppable", *second.unwrap().path().logical_path());
+
+    actor_system.shutdown();
+}
+
+#[test]
+fn incarnations_have_distinct_uids() {
+    let actor_system = ActorSystem::new("test".to_owned());
+    actor_system.spawn_threads(1);
+    let stops = listen(&actor_system, vec![TypeId::of::<ActorStopped>()]);
+
+    let props = Props::new(Arc::new(Stoppable::new), ());
+    let first = actor_system.actor_of(props.clone(), "stoppable".to_owned()).unwrap();
+    // The actor asks to be killed twice, the second request must not kill the next incarnation.
+    actor_system.tell(first.clone(), ());
+    actor_system.tell(first.clone(), ());
+
+    wait_for_event(&stops, "stopped /user/stoppable");
+    let second = actor_system.actor_of(props, "stoppable".to_owned()).unwrap();
+
+    assert_eq!(first.path(), second.path());
+    assert!(first.uid() != second.uid());
+    assert_eq!(Delivery::DeadLetter, actor_system.tell(first, ()));
+    assert_eq!(Some(second.uid()), actor_system.lookup("/user/stoppable").map(|actor| actor.uid()));
+
+    actor_system.shutdown();
+}
+
+#[test]
+fn send_to_stopped_actor() {
+    let actor_system = ActorSystem::new("test".to_owned());
+    actor_system.spawn_threads(1);
+    let stops = listen(&actor_system, vec![TypeId::of::<ActorStopped>()]);
+
+    let props = Props::new(Arc::new(Stoppable::new), ());
+    let stoppable = actor_system.actor_of(props, "stoppable".to_owned()).unwrap();
+    assert_eq!(Delivery::Delivered, actor_system.tell(stoppable.clone(), ()));
+
+    wait_for_event(&stops, "stopped /user/stoppable");
+    assert_eq!(Delivery::DeadLetter, actor_system.tell(stoppable, ()));
+
+    actor_system.shutdown();
+}
+
+#[test]
+fn send_to_distant_actor() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let distant = ActorRef::new_distant(ActorPath::new_distant("/user/distant".to_owned(), "127.0.0.1:12345".to_owned()));
+    assert_eq!(Delivery::Remote, actor_system.tell(distant.clone(), ()));
+    // Distant actors are handled by the remoting layer, this must not panic.
+    distant.handle();
+
+    // A ref without an actor and with a local path can't be delivered to.
+    let missing = ActorRef::new_distant(ActorPath::new_local("/user/missing".to_owned()));
+    assert_eq!(Delivery::DeadLetter, actor_system.tell(missing, ()));
+
+    actor_system.shutdown();
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+struct Resolver;
+
+impl Actor for Resolver {
+    fn receive(&self, message: Box<Any>, context: ActorCell) {
+        let message = match Box::<Any>::downcast::<String>(message) {
+            Ok(message) => {
+                let future = context.identify_actor_anonymous(*message);
+                return context.forward_result_to_future::<Option<ActorRef>>(future, context.sender());
+            },
+            Err(message) => message,
+        };
+        // A path with the name of the future, as with the older API.
+        if let Ok(message) = Box::<Any>::downcast::<(String, String)>(message) {
+            #[allow(deprecated)]
+            let future = context.identify_actor(message.0, message.1);
+            context.forward_result_to_future::<Option<ActorRef>>(future, context.sender());
+        }
+    }
+}
+
+impl Resolver {
+    fn new(_dummy: ()) -> Resolver {
+        Resolver
+    }
+}
+
+#[test]
+fn resolve_name_real_path() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let props = Props::new(Arc::new(Resolver::new), ());
+    let answerer = actor_system.actor_of(props.clone(), "answerer".to_owned()).unwrap();
+    let requester = actor_system.actor_of(props.clone(), "sender".to_owned()).unwrap();
+
+    let res = actor_system.ask_anonymous(answerer, "/user/sender".to_owned());
+    let res: Option<ActorRef> = actor_system.extract_result(res);
+    assert_eq!(requester.path(), res.unwrap().path());
+
+    actor_system.shutdown();
+}
+
+#[test]
+fn resolve_name_fake_path() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let props = Props::new(Arc::new(Resolver::new), ());
+    let answerer = actor_system.actor_of(props.clone(), "answerer".to_owned()).unwrap();
+
+    let res = actor_system.ask_anonymous(answerer, "/foo/bar".to_owned());
+    let res: Option<ActorRef> = actor_system.extract_result(res);
+
+    if res.is_some() {
+        panic!("An ActorRef was given for a path without actor.");
+    }
+
+    actor_system.shutdown();
+}
+
+#[test]
+fn resolve_name_relative_path() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let props = Props::new(Arc::new(Resolver::new), ());
+    let answerer = actor_system.actor_of(props.clone(), "answerer".to_owned()).unwrap();
+    let requester = actor_system.actor_of(props.clone(), "sender".to_owned()).unwrap();
+
+    let res = actor_system.ask_anonymous(answerer.clone(), "../sender".to_owned());
+    let res: Option<ActorRef> = actor_system.extract_result(res);
+    assert_eq!(requester.path(), res.unwrap().path());
+
+    let res = actor_system.ask_anonymous(answerer.clone(), ".".to_owned());
+    let res: Option<ActorRef> = actor_system.extract_result(res);
+    assert_eq!(answerer.path(), res.unwrap().path());
+
+    actor_system.shutdown();
+}
+
+#[test]
+fn lookup_registered_actors() {
+    let actor_system = ActorSystem::new("test".to_owned());
+    let stops = listen(&actor_system, vec![TypeId::of::<ActorStopped>()]);
+
+    let props = Props::new(Arc::new(Stoppable::new), ());
+    let stoppable = actor_system.actor_of(props, "stoppable".to_owned()).unwrap();
+
+    // The registration is done when the actor is created, there is no need to wait.
+    assert_eq!(Some(stoppable.path()), actor_system.lookup("/user/stoppable").map(|actor| actor.path()));
+    assert!(actor_system.lookup("/system/name_resolver").is_some());
+    assert!(actor_system.lookup("/user/missing").is_none());
+    // The root actors are not registered, they are found by walking the actor tree.
+    assert_eq!("/user", *actor_system.lookup("/user").unwrap().path().logical_path());
+    assert_eq!("/system", *actor_system.lookup("/system/").unwrap().path().logical_path());
+    // The name resolver answers with the same registry.
+    let resolved = actor_system.ask_anonymous(actor_system.name_resolver(), ResolveRequest::Get("/user/stoppable".to_owned()));
+    let resolved: Option<ActorRef> = actor_system.extract_result(resolved);
+    assert_eq!(stoppable.path(), resolved.unwrap().path());
+
+    actor_system.tell(stoppable.clone(), ());
+    // The actor is unregistered before its stop is published.
+    wait_for_event(&stops, "stopped /user/stoppable");
+    assert!(actor_system.lookup("/user/stoppable").is_none());
+
+    actor_system.shutdown();
+}
+
+/// Creates a child for each of the given names, and sends its path to the channel when it gets a
+/// message.
+struct Selectable {
+    children: Vec<String>,
+    sender: Arc<Mutex<Sender<String>>>,
+}
+
+impl Actor for Selectable {
+    fn pre_start(&self, context: ActorCell) {
+        for name in self.children.iter() {
+            let props = Props::new(Arc::new(Selectable::new), (Vec::new(), self.sender.clone()));
+            context.actor_of(props, name.clone()).unwrap();
+        }
+    }
+
+    fn receive(&self, _message: Box<Any>, context: ActorCell) {
+        let _ = self.sender.lock().unwrap().send(context.path().logical_path().clone());
+    }
+}
+
+impl Selectable {
+    fn new((children, sender): (Vec<String>, Arc<Mutex<Sender<String>>>)) -> Selectable {
+        Selectable {
+            children: children,
+            sender: sender,
+        }
+    }
+}
+
+fn selected_paths(actor_system: &ActorSystem, pattern: &str) -> Vec<String> {
+    let res = actor_system.select(pattern.to_owned());
+    let res: Vec<ActorRef> = actor_system.extract_result(res);
+    res.iter().map(|actor| actor.path().logical_path().clone()).collect()
+}
+
+#[test]
+fn select_with_wildcards() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let (tx, _rx) = channel();
+    let tx = Arc::new(Mutex::new(tx));
+    let workers = vec!["a".to_owned(), "b".to_owned(), "db".to_owned()];
+    actor_system.actor_of(Props::new(Arc::new(Selectable::new), (workers, tx.clone())), "workers".to_owned()).unwrap();
+    actor_system.actor_of(Props::new(Arc::new(Selectable::new), (Vec::new(), tx)), "db".to_owned()).unwrap();
+
+    // The workers are created by their father when it starts.
+    wait_until("the workers are created", || actor_system.lookup("/user/workers/db").is_some());
+
+    assert_eq!(vec!["/user/workers/a", "/user/workers/b", "/user/workers/db"],
+               selected_paths(&actor_system, "/user/workers/*"));
+    assert_eq!(vec!["/user/db", "/user/workers/db"],
+               selected_paths(&actor_system, "/user/**/db"));
+    assert_eq!(vec!["/user/db"],
+               selected_paths(&actor_system, "/user/workers/../d*"));
+    assert!(selected_paths(&actor_system, "/user/nothing/*").is_empty());
+    // The Futures used for the selections are anonymous children of the user actor.
+    assert_eq!(vec!["/user/db", "/user/workers"], selected_paths(&actor_system, "/user/*"));
+
+    actor_system.shutdown();
+}
+
+#[test]
+fn broadcast_to_selection() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let (tx, rx) = channel();
+    let tx = Arc::new(Mutex::new(tx));
+    let workers = vec!["a".to_owned(), "b".to_owned()];
+    actor_system.actor_of(Props::new(Arc::new(Selectable::new), (workers, tx)), "workers".to_owned()).unwrap();
+
+    // The workers are created by their father when it starts.
+    wait_until("the workers are created", || actor_system.lookup("/user/workers/b").is_some());
+
+    actor_system.broadcast("/user/workers/*".to_owned(), ());
+    let mut received = vec![rx.recv().unwrap(), rx.recv().unwrap()];
+    received.sort();
+    assert_eq!(vec!["/user/workers/a", "/user/workers/b"], received);
+
+    actor_system.shutdown();
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+struct SimpleActor1 {
+    sender: Arc<Mutex<Sender<Res>>>,
+}
+
+impl Actor for SimpleActor1 {
+    fn receive(&self, _message: Box<Any>, _context: ActorCell) {
+        let _ = self.sender.lock().unwrap().send(Res::Ok);
+    }
+}
+
+impl SimpleActor1 {
+    fn new(sender: Arc<Mutex<Sender<Res>>>) -> SimpleActor1 {
+        SimpleActor1 {
+            sender: sender,
+        }
+    }
+}
+
+#[test]
+fn receive_message () {
+    env_logger::init().unwrap();
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let (tx, rx) = channel();
+    let tx = Arc::new(Mutex::new(tx));
+
+    let props = Props::new(Arc::new(SimpleActor1::new), tx);
+    let actor_ref = actor_system.actor_of(props.clone(), "actor".to_owned()).unwrap();
+
+    actor_system.tell(actor_ref, ());
+
+    let res = rx.recv();
+    assert_eq!(Ok(Res::Ok), res);
+
+    actor_system.shutdown();
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+struct SimpleActor2 {
+    sender: Arc<Mutex<Sender<Res>>>,
+    state: i32,
+}
+
+impl Actor for SimpleActor2 {
+    fn receive(&self, message: Box<Any>, _context: ActorCell) {
+        if let Ok(message) = Box::<Any>::downcast::<i32>(message) {
+            if *message == self.state {
+                let _ = self.sender.lock().unwrap().send(Res::Ok);
+            } else {
+                let _ = self.sender.lock().unwrap().send(Res::Err);
+            }
+        }
+    }
+}
+
+impl SimpleActor2 {
+    fn new(args: (Arc<Mutex<Sender<Res>>>, i32)) -> SimpleActor2 {
+        SimpleActor2 {
+            sender: args.0,
+            state: args.1,
+        }
+    }
+}
+
+#[test]
+fn receive_correct_message () {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let (tx, rx) = channel();
+    let tx = Arc::new(Mutex::new(tx));
+    let value = 42;
+
+    let props = Props::new(Arc::new(SimpleActor2::new), (tx, value));
+    let actor_ref = actor_system.actor_of(props.clone(), "actor".to_owned()).unwrap();
+
+    actor_system.tell(actor_ref, value);
+
+    let res = rx.recv();
+    assert_eq!(Ok(Res::Ok), res);
+
+    actor_system.shutdown();
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+struct SimpleActor3;
+
+impl Actor for SimpleActor3 {
+    fn receive(&self, _message: Box<Any>, _context: ActorCell) {
+        panic!("Panic as planned, should start an failure handler.");
+    }
+}
+
+impl SimpleActor3 {
+    fn new(_args: ()) -> SimpleActor3 {
+        SimpleActor3
+    }
+}
+
+#[derive(Clone)]
+enum SimpleActor4Messages {
+    RegisterMe,
+    ActorFailure,
+}
+
+struct SimpleActor4 {
+    sender: Arc<Mutex<Sender<Res>>>,
+}
+
+impl Actor for SimpleActor4 {
+    fn receive(&self, message: Box<Any>, context: ActorCell) {
+        if let Ok(message) = Box::<Any>::downcast::<SimpleActor4Messages>(message) {
+            match *message {
+                SimpleActor4Messages::RegisterMe => {
+                    context.monitor(context.sender(), Arc::new(|_failure, context| {
+                        // When the monitored actor failed, the monitorer pipes a message to itself.
+                        context.tell(context.actor_ref(), SimpleActor4Messages::ActorFailure);
+                    }));
+                    context.tell(context.sender(), ());
+                },
+                SimpleActor4Messages::ActorFailure => {
+                    let _ = self.sender.lock().unwrap().send(Res::Ok);
+                }
+            }
+        }
+    }
+}
+
+impl SimpleActor4 {
+    fn new(args: Arc<Mutex<Sender<Res>>>) -> SimpleActor4 {
+        SimpleActor4 {
+            sender: args
+        }
+    }
+}
+
+#[test]
+fn receive_failure_notifications () {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let (tx, rx) = channel();
+    let tx = Arc::new(Mutex::new(tx));
+
+    let props_panicker = Props::new(Arc::new(SimpleActor3::new), ());
+    let props_handler = Props::new(Arc::new(SimpleActor4::new), tx);
+    let panicker = actor_system.actor_of(props_panicker.clone(), "panicker".to_owned()).unwrap();
+    let handler = actor_system.actor_of(props_handler.clone(), "handler".to_owned()).unwrap();
+
+    panicker.tell_to(handler.clone(), SimpleActor4Messages::RegisterMe);
+
+    let res = rx.recv();
+    assert_eq!(Ok(Res::Ok), res);
+
+    actor_system.shutdown();
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+struct SimpleActor5;
+
+impl Actor for SimpleActor5 {
+    fn receive(&self, _message: Box<Any>, context: ActorCell) {
+        context.fail("failure");
+    }
+}
+
+impl SimpleActor5 {
+    fn new(_args: ()) -> SimpleActor5 {
+        SimpleActor5
+    }
+}
+
+#[derive(Clone)]
+enum SimpleActor6Messages {
+    RegisterMe,
+    ActorFailureOk,
+    ActorFailureErr,
+}
+
+struct SimpleActor6 {
+    sender: Arc<Mutex<Sender<Res>>>,
+}
+
+impl Actor for SimpleActor6 {
+    fn receive(&self, message: Box<Any>, context: ActorCell) {
+        if let Ok(message) = Box::<Any>::downcast::<SimpleActor6Messages>(message) {
+            match *message {
+                SimpleActor6Messages::RegisterMe => {
+                    context.monitor(context.sender(), Arc::new(|failure, context| {
+                        // When the monitored actor failed, the monitorer pipes a message to itself.
+                        match *failure.reason() {
+                            RobotsError::Failed(ref reason) if reason == "failure" => {
+                                context.tell(context.actor_ref(), SimpleActor6Messages::ActorFailureOk)
+                            },
+                            _ => context.tell(context.actor_ref(), SimpleActor6Messages::ActorFailureErr),
+                        };
+                    }));
+                    context.tell(context.sender(), ());
+                },
+                SimpleActor6Messages::ActorFailureOk => {
+                    let _ = self.sender.lock().unwrap().send(Res::Ok);
+                },
+                SimpleActor6Messages::ActorFailureErr => {
+                    let _ = self.sender.lock().unwrap().send(Res::Err);
+                },
+            }
+        }
+    }
+}
+
+impl SimpleActor6 {
+    fn new(args: Arc<Mutex<Sender<Res>>>) -> SimpleActor6 {
+        SimpleActor6 {
+            sender: args
+        }
+    }
+}
+
+#[test]
+fn receive_failure_reason_ok () {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let (tx, rx) = channel();
+    let tx = Arc::new(Mutex::new(tx));
+
+    let props_panicker = Props::new(Arc::new(SimpleActor5::new), ());
+    let props_handler = Props::new(Arc::new(SimpleActor6::new), tx);
+    let panicker = actor_system.actor_of(props_panicker.clone(), "panicker".to_owned()).unwrap();
+    let handler = actor_system.actor_of(props_handler.clone(), "handler".to_owned()).unwrap();
+
+    panicker.tell_to(handler.clone(), SimpleActor6Messages::RegisterMe);
+
+    let res = rx.recv();
+    assert_eq!(Ok(Res::Ok), res);
+
+    actor_system.shutdown();
+}
+
+#[test]
+fn receive_failure_reason_err () {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let (tx, rx) = channel();
+    let tx = Arc::new(Mutex::new(tx));
+
+    // This actors does not fail with the "failure" reason, so the reason will not be correct
+    let props_panicker = Props::new(Arc::new(SimpleActor3::new), ());
+    let props_handler = Props::new(Arc::new(SimpleActor6::new), tx);
+    let panicker = actor_system.actor_of(props_panicker.clone(), "panicker".to_owned()).unwrap();
+    let handler = actor_system.actor_of(props_handler.clone(), "handler".to_owned()).unwrap();
+
+    panicker.tell_to(handler.clone(), SimpleActor6Messages::RegisterMe);
+
+    let res = rx.recv();
+    assert_eq!(Ok(Res::Err), res);
+
+    actor_system.shutdown();
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Creates a Router of `size` routees sending their path to the returned channel when they get a
+/// message.
+fn router(actor_system: &ActorSystem, strategy: RoutingStrategy, size: usize) -> (ActorRef, Receiver<String>) {
+    let (tx, rx) = channel();
+    let routee_props = Props::new(Arc::new(Selectable::new), (Vec::new(), Arc::new(Mutex::new(tx))));
+    let router = actor_system.actor_of(Router::<u32>::pool(strategy, size, routee_props), "router".to_owned()).unwrap();
+    (router, rx)
+}
+
+/// Counts the messages received by each routee.
+fn received_by_routees(rx: &Receiver<String>, n: usize) -> HashMap<String, usize> {
+    let mut received = HashMap::new();
+    for _ in 0..n {
+        *received.entry(rx.recv().unwrap()).or_insert(0) += 1;
+    }
+    received
+}
+
+#[test]
+fn round_robin_router() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let (router, rx) = router(&actor_system, RoutingStrategy::RoundRobin, 3);
+    for i in 0..6 {
+        actor_system.tell(router.clone(), i as u32);
+    }
+
+    let received = received_by_routees(&rx, 6);
+    assert_eq!(3, received.len());
+    assert!(received.values().all(|&n| n == 2));
+
+    actor_system.shutdown();
+}
+
+#[test]
+fn random_and_smallest_mailbox_routers() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    for (name, strategy) in vec![("random", RoutingStrategy::Random), ("smallest", RoutingStrategy::SmallestMailbox)] {
+        let (tx, rx) = channel();
+        let routee_props = Props::new(Arc::new(Selectable::new), (Vec::new(), Arc::new(Mutex::new(tx))));
+        let router = actor_system.actor_of(Router::<u32>::pool(strategy, 3, routee_props), name.to_owned()).unwrap();
+        for i in 0..10 {
+            actor_system.tell(router.clone(), i as u32);
+        }
+        let received = received_by_routees(&rx, 10);
+        assert!(received.keys().all(|path| path.starts_with(&format!("/user/{}/$", name))));
+    }
+
+    actor_system.shutdown();
+}
+
+#[test]
+fn broadcast_router() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let (router, rx) = router(&actor_system, RoutingStrategy::Broadcast, 3);
+    actor_system.tell(router.clone(), 0u32);
+    assert_eq!(3, received_by_routees(&rx, 3).len());
+
+    // Any router can broadcast a message.
+    let (router, rx) = {
+        let (tx, rx) = channel();
+        let routee_props = Props::new(Arc::new(Selectable::new), (Vec::new(), Arc::new(Mutex::new(tx))));
+        let props = Router::<u32>::pool(RoutingStrategy::RoundRobin, 2, routee_props);
+        (actor_system.actor_of(props, "round_robin".to_owned()).unwrap(), rx)
+    };
+    actor_system.tell(router, Broadcast(0u32));
+    assert_eq!(2, received_by_routees(&rx, 2).len());
+
+    actor_system.shutdown();
+}
+
+#[test]
+fn resize_router() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let (router, _rx) = router(&actor_system, RoutingStrategy::RoundRobin, 3);
+
+    actor_system.tell(router.clone(), RouterMessage::AdjustPoolSize(2));
+    let routees = actor_system.ask_anonymous(router.clone(), RouterMessage::GetRoutees);
+    let routees: Vec<ActorRef> = actor_system.extract_result(routees);
+    assert_eq!(5, routees.len());
+
+    actor_system.tell(router.clone(), RouterMessage::AdjustPoolSize(-4));
+    let routees = actor_system.ask_anonymous(router.clone(), RouterMessage::GetRoutees);
+    let routees: Vec<ActorRef> = actor_system.extract_result(routees);
+    assert_eq!(1, routees.len());
+
+    actor_system.tell(router.clone(), RouterMessage::AdjustPoolSize(i32::MIN));
+    let routees = actor_system.ask_anonymous(router.clone(), RouterMessage::GetRoutees);
+    let routees: Vec<ActorRef> = actor_system.extract_result(routees);
+    assert_eq!(0, routees.len());
+
+    actor_system.shutdown();
+}
+
+#[test]
+fn group_router() {
+    let actor_system = ActorSystem::new("test".to_owned());
+    let stops = listen(&actor_system, vec![TypeId::of::<ActorStopped>()]);
+
+    let (tx, rx) = channel();
+    let selectable_props = Props::new(Arc::new(Selectable::new), (Vec::new(), Arc::new(Mutex::new(tx))));
+    actor_system.actor_of(selectable_props, "selectable".to_owned()).unwrap();
+    actor_system.actor_of(Props::new(Arc::new(Stoppable::new), ()), "stoppable".to_owned()).unwrap();
+
+    // Paths are resolved from the path of the router, and those of missing actors are skipped.
+    let paths = vec!["../selectable".to_owned(), "/user/stoppable".to_owned(), "/user/missing".to_owned()];
+    let router = actor_system.actor_of(Router::<u32>::group(RoutingStrategy::RoundRobin, paths), "group".to_owned()).unwrap();
+    let routees = actor_system.ask_anonymous(router.clone(), RouterMessage::GetRoutees);
+    let routees: Vec<ActorRef> = actor_system.extract_result(routees);
+    assert_eq!(2, routees.len());
+
+    // Terminated actors are no longer routees.
+    actor_system.tell(router.clone(), Broadcast(0u32));
+    assert_eq!("/user/selectable", rx.recv().unwrap());
+    wait_for_event(&stops, "stopped /user/stoppable");
+    for i in 0..4 {
+        actor_system.tell(router.clone(), i as u32);
+    }
+    let received = received_by_routees(&rx, 4);
+    assert_eq!(Some(&4), received.get("/user/selectable"));
+
+    actor_system.shutdown();
+}
+
+/// Takes some time to handle each message, then sends its path to the channel.
+struct Slow {
+    sender: Arc<Mutex<Sender<String>>>,
+}
+
+impl Actor for Slow {
+    fn receive(&self, _message: Box<Any>, context: ActorCell) {
+        thread::sleep(Duration::from_millis(5));
+        let _ = self.sender.lock().unwrap().send(context.path().logical_path().clone());
+    }
+}
+
+impl Slow {
+    fn new(sender: Arc<Mutex<Sender<String>>>) -> Slow {
+        Slow {
+            sender: sender,
+        }
+    }
+}
+
+#[test]
+fn resizable_router() {
+    let actor_system = ActorSystem::new("test".to_owned());
+    actor_system.spawn_threads(3);
+
+    let (tx, rx) = channel();
+    let mut resizer = Resizer::new(1, 4);
+    resizer.rampup_rate = 1.0;
+    resizer.messages_per_resize = 1;
+    let routee_props = Props::new(Arc::new(Slow::new), Arc::new(Mutex::new(tx)));
+    let props = Router::<u32>::resizable_pool(RoutingStrategy::RoundRobin, resizer, routee_props);
+    let router = actor_system.actor_of(props, "router".to_owned()).unwrap();
+    let routees = |router: &ActorRef| {
+        let routees = actor_system.ask_anonymous(router.clone(), RouterMessage::GetRoutees);
+        let routees: Vec<ActorRef> = actor_system.extract_result(routees);
+        routees.len()
+    };
+    assert_eq!(1, routees(&router));
+
+    // The pool grows under pressure, within its bounds.
+    for i in 0..40 {
+        actor_system.tell(router.clone(), i as u32);
+    }
+    received_by_routees(&rx, 40);
+    let size = routees(&router);
+    assert!(size > 1 && size <= 4);
+
+    // And shrinks back when the routees are idle.
+    for i in 0..10 {
+        actor_system.tell(router.clone(), i as u32);
+        rx.recv().unwrap();
+    }
+    assert_eq!(1, routees(&router));
+
+    actor_system.shutdown();
+}
+
+/// Sends the keys it gets to the channel, along with its path.
+struct KeyOwner {
+    sender: Arc<Mutex<Sender<(u32, String)>>>,
+}
+
+impl Actor for KeyOwner {
+    fn receive(&self, message: Box<Any>, context: ActorCell) {
+        if let Ok(key) = Box::<Any>::downcast::<u32>(message) {
+            let _ = self.sender.lock().unwrap().send((*key, context.path().logical_path().clone()));
+        }
+    }
+}
+
+impl KeyOwner {
+    fn new(sender: Arc<Mutex<Sender<(u32, String)>>>) -> KeyOwner {
+        KeyOwner {
+            sender: sender,
+        }
+    }
+}
+
+/// Gives the routee that got each of the keys.
+fn key_owners(actor_system: &ActorSystem, router: &ActorRef, rx: &Receiver<(u32, String)>) -> HashMap<u32, String> {
+    for key in 0..100u32 {
+        actor_system.tell(router.clone(), key);
+    }
+    (0..100).map(|_| rx.recv().unwrap()).collect()
+}
+
+#[test]
+fn consistent_hashing_router() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let (tx, rx) = channel();
+    let routee_props = Props::new(Arc::new(KeyOwner::new), Arc::new(Mutex::new(tx)));
+    let props = Router::<u32>::consistent_hashing(4, routee_props, |key: &u32| *key);
+    let router = actor_system.actor_of(props, "router".to_owned()).unwrap();
+
+    let owners = key_owners(&actor_system, &router, &rx);
+    assert_eq!(owners, key_owners(&actor_system, &router, &rx));
+
+    // Only keys taken by the new routee move.
+    actor_system.tell(router.clone(), RouterMessage::AdjustPoolSize(1));
+    let new_owners = key_owners(&actor_system, &router, &rx);
+    let old_routees: Vec<&String> = owners.values().collect();
+    let moved: Vec<u32> = (0..100).filter(|key| owners[key] != new_owners[key]).collect();
+    assert!(!moved.is_empty());
+    assert!(moved.iter().all(|key| !old_routees.contains(&&new_owners[key])));
+
+    actor_system.shutdown();
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Subscribes to the given classes of events and describes the events it gets to the channel.
+struct Listener {
+    classes: Vec<TypeId>,
+    sender: Arc<Mutex<Sender<String>>>,
+}
+
+impl Actor for Listener {
+    fn pre_start(&self, context: ActorCell) {
+        for class in self.classes.iter() {
+            context.subscribe(*class);
+        }
+    }
+
+    fn receive(&self, message: Box<Any>, _context: ActorCell) {
+        let description = if let Some(event) = message.downcast_ref::<String>() {
+            event.clone()
+        } else if let Some(event) = message.downcast_ref::<ActorStarted>() {
+            format!("started {}", event.actor.path().logical_path())
+        } else if let Some(event) = message.downcast_ref::<ActorStopped>() {
+            format!("stopped {}", event.path.logical_path())
+        } else if let Some(event) = message.downcast_ref::<ActorFailed>() {
+            format!("failed {}: {}", event.actor.path().logical_path(), event.reason)
+        } else if let Some(event) = message.downcast_ref::<DeadLetter>() {
+            format!("dead letter to {}", event.recipient.path().logical_path())
+        } else if let Some(event) = message.downcast_ref::<MemberUp>() {
+            format!("up {}", event.member.address)
+        } else if let Some(event) = message.downcast_ref::<MemberLeft>() {
+            format!("left {}", event.member.address)
+        } else if let Some(event) = message.downcast_ref::<MemberRemoved>() {
+            format!("removed {}", event.member.address)
+        } else if let Some(event) = message.downcast_ref::<UnreachableMember>() {
+            format!("unreachable {}", event.member.address)
+        } else {
+            return;
+        };
+        let _ = self.sender.lock().unwrap().send(description);
+    }
+}
+
+impl Listener {
+    fn new((classes, sender): (Vec<TypeId>, Arc<Mutex<Sender<String>>>)) -> Listener {
+        Listener {
+            classes: classes,
+            sender: sender,
+        }
+    }
+}
+
+/// Gives a channel describing the events of the given classes, they are subscribed to once this
+/// returns.
+fn listen(actor_system: &ActorSystem, classes: Vec<TypeId>) -> Receiver<String> {
+    let (tx, rx) = channel();
+    let listener = actor_system.actor_of_anonymous(Props::new(Arc::new(Listener::new), (Vec::new(), Arc::new(Mutex::new(tx)))));
+    for class in classes {
+        actor_system.event_stream().subscribe(listener.clone(), class);
+    }
+    rx
+}
+
+/// Waits for the expected event, skipping the others.
+fn wait_for_event(rx: &Receiver<String>, expected: &str) {
+    loop {
+        match rx.recv_timeout(Duration::from_secs(5)) {
+            Ok(ref event) if event == expected => return,
+            Ok(_) => continue,
+            Err(_) => panic!("Did not get the event: {}", expected),
+        }
+    }
+}
+
+/// Class of events used in the tests.
+enum Greeting {}
+
+#[test]
+fn publish_and_subscribe() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let (tx, rx) = channel();
+    let props = Props::new(Arc::new(Listener::new), (Vec::new(), Arc::new(Mutex::new(tx))));
+    let listener = actor_system.actor_of(props, "listener".to_owned()).unwrap();
+    actor_system.event_stream().subscribe(listener, TypeId::of::<String>());
+    let (tx, greeting_rx) = channel();
+    let props = Props::new(Arc::new(Listener::new), (Vec::new(), Arc::new(Mutex::new(tx))));
+    let greeting_listener = actor_system.actor_of(props, "greeting_listener".to_owned()).unwrap();
+    actor_system.event_stream().subscribe(greeting_listener, TypeId::of::<Greeting>());
+
+    actor_system.event_stream().publish("hello".to_owned());
+    assert_eq!(Ok("hello".to_owned()), rx.recv_timeout(Duration::from_secs(5)));
+    // Events are also sent to the subscribers of their classes.
+    actor_system.event_stream().add_class(TypeId::of::<String>(), TypeId::of::<Greeting>());
+    actor_system.event_stream().publish("hi".to_owned());
+    assert_eq!(Ok("hi".to_owned()), rx.recv_timeout(Duration::from_secs(5)));
+    assert_eq!(Ok("hi".to_owned()), greeting_rx.recv_timeout(Duration::from_secs(5)));
+    actor_system.event_stream().publish(0u32);
+    assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+
+    actor_system.shutdown();
+}
+
+#[test]
+fn system_events() {
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let (tx, rx) = channel();
+    let props = Props::new(Arc::new(Listener::new), (vec![TypeId::of::<SystemEvent>()], Arc::new(Mutex::new(tx))));
+    actor_system.actor_of(props, "listener".to_owned()).unwrap();
+    wait_for_event(&rx, "started /user/listener");
+
+    let stoppable = actor_system.actor_of(Props::new(Arc::new(Stoppable::new), ()), "stoppable".to_owned()).unwrap();
+    wait_for_event(&rx, "started /user/stoppable");
+    actor_system.tell(stoppable.clone(), ());
+    wait_for_event(&rx, "stopped /user/stoppable");
+    actor_system.tell(stoppable, ());
+    wait_for_event(&rx, "dead letter to /user/stoppable");
+
+    let failing = actor_system.actor_of(Props::new(Arc::new(SimpleActor3::new), ()), "failing".to_owned()).unwrap();
+    actor_system.tell(failing, ());
+    wait_for_event(&rx, "failed /user/failing: the actor panicked");
+
+    actor_system.shutdown();
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Persistent counter adding the u32 it gets, it answers its total to "total", panics on "panic",
+/// stops on "stop" and answers whether it saved a snapshot to "snapshot".
+struct Counter {
+    persistence: Persistence,
+    total: Mutex<u32>,
+}
+
+impl PersistentActor for Counter {
+    type Event = u32;
+    type Snapshot = u32;
+
+    fn persistence(&self) -> &Persistence {
+        &self.persistence
+    }
+
+    fn receive_command(&self, command: Box<Any>, context: ActorCell) {
+        let command = match Box::<Any>::downcast::<u32>(command) {
+            Ok(n) => return self.persist(*n, context, |n| *self.total.lock().unwrap() += n),
+            Err(command) => command,
+        };
+        if let Ok(command) = Box::<Any>::downcast::<&'static str>(command) {
+            match *command {
+                "total" => context.complete(context.sender(), *self.total.lock().unwrap()),
+                "panic" => panic!("Panic as planned, the counter is restarted"),
+                "stop" => context.kill_me(),
+                "snapshot" => context.complete(context.sender(), self.save_snapshot(&*self.total.lock().unwrap()).is_ok()),
+                _ => {},
+            }
+        }
+    }
+
+    fn receive_recover(&self, event: u32, _context: ActorCell) {
+        *self.total.lock().unwrap() += event;
+    }
+
+    fn receive_snapshot(&self, snapshot: u32, _context: ActorCell) {
+        *self.total.lock().unwrap() = snapshot;
+    }
+}
+
+impl Counter {
+    fn new((persistence_id, journal, snapshots): (String, Arc<Journal>, Option<(Arc<SnapshotStore>, Retention)>))
+           -> Counter {
+        let persistence = match snapshots {
+            Some((snapshot_store, retention)) => {
+                Persistence::with_snapshots(persistence_id, journal, snapshot_store, retention)
+            },
+            None => Persistence::new(persistence_id, journal),
+        };
+        Counter {
+            persistence: persistence,
+            total: Mutex::new(0),
+        }
+    }
+}
+
+fn counter_total(actor_system: &ActorSystem, counter: &ActorRef) -> u32 {
+    let total = actor_system.ask_anonymous(counter.clone(), "total");
+    actor_system.extract_result(total)
+}
+
+#[test]
+fn persistent_actor_recovery() {
+    let actor_system = ActorSystem::new("test".to_owned());
+    let stops = listen(&actor_system, vec![TypeId::of::<ActorStopped>()]);
+
+    let journal: Arc<Journal> = Arc::new(InMemoryJournal::new());
+    let props = Persistent::props(Arc::new(Counter::new), ("counter".to_owned(), journal.clone(), None));
+    let counter = actor_system.actor_of(props.clone(), "counter".to_owned()).unwrap();
+    for n in 1..4u32 {
+        actor_system.tell(counter.clone(), n);
+    }
+    assert_eq!(6, counter_total(&actor_system, &counter));
+
+    // The restarted actor replays its events.
+    actor_system.tell(counter.clone(), "panic");
+    assert_eq!(6, counter_total(&actor_system, &counter));
+    actor_system.tell(counter.clone(), 4u32);
+    assert_eq!(10, counter_total(&actor_system, &counter));
+
+    // And so does another actor with the same persistence id.
+    actor_system.tell(counter.clone(), "stop");
+    wait_for_event(&stops, "stopped /user/counter");
+    let counter = actor_system.actor_of(props, "other_counter".to_owned()).unwrap();
+    assert_eq!(10, counter_total(&actor_system, &counter));
+
+    actor_system.shutdown();
+}
+
+#[test]
+fn file_journal() {
+    let directory = env::temp_dir().join(format!("robots_file_journal_{}", process::id()));
+    let _ = fs::remove_dir_all(&directory);
+
+    {
+        let actor_system = ActorSystem::new("test".to_owned());
+        let journal: Arc<Journal> = Arc::new(FileJournal::new(&directory).unwrap());
+        let props = Persistent::props(Arc::new(Counter::new), ("counter".to_owned(), journal, None));
+        let counter = actor_system.actor_of(props, "counter".to_owned()).unwrap();
+        for n in 1..4u32 {
+            actor_system.tell(counter.clone(), n);
+        }
+        assert_eq!(6, counter_total(&actor_system, &counter));
+        actor_system.shutdown();
+    }
+
+    // An event that was not completely written is dropped.
+    let journal_file = fs::read_dir(&directory).unwrap().next().unwrap().unwrap().path();
+    OpenOptions::new().append(true).open(&journal_file).unwrap().write_all(&[4, 0, 0]).unwrap();
+
+    let actor_system = ActorSystem::new("test".to_owned());
+    let journal: Arc<Journal> = Arc::new(FileJournal::new(&directory).unwrap());
+    let props = Persistent::props(Arc::new(Counter::new), ("counter".to_owned(), journal.clone(), None));
+    let counter = actor_system.actor_of(props, "counter".to_owned()).unwrap();
+    assert_eq!(6, counter_total(&actor_system, &counter));
+    actor_system.tell(counter.clone(), 4u32);
+    assert_eq!(10, counter_total(&actor_system, &counter));
+    assert_eq!(vec![1, 2, 3, 4], journal.read("counter", 1).unwrap().iter().map(|event| event.0).collect::<Vec<u64>>());
+    journal.delete_to("counter", 2).unwrap();
+    assert_eq!(vec![3, 4], journal.read("counter", 1).unwrap().iter().map(|event| event.0).collect::<Vec<u64>>());
+    actor_system.shutdown();
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn snapshots() {
+    let directory = env::temp_dir().join(format!("robots_snapshots_{}", process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    let actor_system = ActorSystem::new("test".to_owned());
+
+    let journal: Arc<Journal> = Arc::new(InMemoryJournal::new());
+    let snapshot_store: Arc<SnapshotStore> = Arc::new(FileSnapshotStore::new(&directory).unwrap());
+    let snapshots = Some((snapshot_store, Retention::new(1, true)));
+    let props = Persistent::props(Arc::new(Counter::new), ("counter".to_owned(), journal.clone(), snapshots));
+    let counter = actor_system.actor_of(props.clone(), "counter".to_owned()).unwrap();
+    let snapshot = |counter: &ActorRef| {
+        let saved = actor_system.ask_anonymous(counter.clone(), "snapshot");
+        actor_system.extract_result::<bool>(saved)
+    };
+    for n in 1..4u32 {
+        actor_system.tell(counter.clone(), n);
+    }
+    assert!(snapshot(&counter));
+    actor_system.tell(counter.clone(), 4u32);
+    actor_system.tell(counter.clone(), 5u32);
+    assert!(snapshot(&counter));
+    actor_system.tell(counter.clone(), 6u32);
+    assert_eq!(21, counter_total(&actor_system, &counter));
+
+    // Only the latest snapshot and the events after it are kept.
+    assert_eq!(1, fs::read_dir(&directory).unwrap().count());
+    assert_eq!(vec![6], journal.read("counter", 1).unwrap().iter().map(|event| event.0).collect::<Vec<u64>>());
+
+    // The recovery starts from the snapshot.
+    actor_system.tell(counter.clone(), "panic");
+    assert_eq!(21, counter_total(&actor_system, &counter));
+    actor_system.tell(counter.clone(), 7u32);
+    assert_eq!(28, counter_total(&actor_system, &counter));
+    assert_eq!(vec![6, 7], journal.read("counter", 1).unwrap().iter().map(|event| event.0).collect::<Vec<u64>>());
+
+    actor_system.shutdown();
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+/// Sends the u32 it receives to the channel and panics on 0, it answers `()` once it handled the
+/// previous messages.
+struct Forwarder {
+    sender: Arc<Mutex<Sender<u32>>>,
+}
+
+impl Actor for Forwarder {
+    fn receive(&self, message: Box<Any>, context: ActorCell) {
+        if let Ok(message) = Box::<Any>::downcast::<u32>(message) {
+            if *message == 0 {
+                panic!("Panic as planned, the forwarder is restarted");
+            }
+            let _ = self.sender.lock().unwrap().send(*message);
+        } else {
+            context.complete(context.sender(), ());
+        }
+    }
+}
+
+impl Forwarder {
+    fn new(sender: Arc<Mutex<Sender<u32>>>) -> Forwarder {
+        Forwarder {
+            sender: sender,
+        }
+    }
+}
+
+#[test]
+fn durable_mailbox() {
+    let directory = env::temp_dir().join(format!("robots_durable_mailbox_{}", process::id()));
+    let _ = fs::remove_dir_all(&directory);
+
+    // Messages left by a process which stopped before its actor handled them.
+    {
+        let storage = DurableMailbox::<u32>::new(&directory).unwrap();
+        assert_eq!(None, storage.append("/user/forwarder", &"not stored".to_owned()).unwrap());
+        assert_eq!(Some(0), storage.append("/user/forwarder", &1u32).unwrap());
+        assert_eq!(Some(1), storage.append("/user/forwarder", &2u32).unwrap());
+        assert_eq!(Some(2), storage.append("/user/forwarder", &3u32).unwrap());
+        storage.acknowledge("/user/forwarder", 0).unwrap();
+    }
+
+    let actor_system = ActorSystem::new("test".to_owned());
+    let storage: Arc<MailboxStorage> = Arc::new(DurableMailbox::<u32>::new(&directory).unwrap());
+    let (tx, rx) = channel();
+    let props = Props::with_mailbox(Arc::new(Forwarder::new), Arc::new(Mutex::new(tx)), storage.clone());
+    let forwarder = actor_system.actor_of(props, "forwarder".to_owned()).unwrap();
+    actor_system.tell(forwarder.clone(), 4u32);
+    let received: Vec<u32> = (0..3).map(|_| rx.recv_timeout(Duration::from_secs(1)).unwrap()).collect();
+    assert_eq!(vec![2, 3, 4], received);
+
+    // The restarted actor keeps handling the next messages.
+    actor_system.tell(forwarder.clone(), 0u32);
+    actor_system.tell(forwarder.clone(), 5u32);
+    assert_eq!(5, rx.recv_timeout(Duration::from_secs(1)).unwrap());
+    let handled = actor_system.ask_anonymous(forwarder, ());
+    actor_system.extract_result_timeout::<()>(handled, Duration::from_secs(5)).unwrap();
+    actor_system.shutdown();
+
+    // Only the message which made the actor panic was not acknowledged, it is given again.
+    let recovered: Vec<u32> = storage.recover("/user/forwarder")
+                                     .unwrap()
+                                     .into_iter()
+                                     .map(|(_, message)| *message.downcast::<u32>().unwrap())
+                                     .collect();
+    assert_eq!(vec![0], recovered);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+/// Message delivered at least once by a Reliable actor.
+#[derive(Clone)]
+struct Reliable(u64, String);
+
+/// Confirmation of a Reliable message.
+#[derive(Clone, Copy)]
+struct Confirm(u64);
+
+/// Delivers the strings it receives at least once to its recipient, and answers to a `()` with the
+/// number of unconfirmed messages.
+struct ReliableSender {
+    recipient: ActorRef,
+    deliveries: AtLeastOnceDelivery,
+}
+
+impl Actor for ReliableSender {
+    fn receive(&self, message: Box<Any>, context: ActorCell) {
+        let message = match Box::<Any>::downcast::<String>(message) {
+            Ok(message) => {
+                let delivered = self.deliveries.deliver(&context, self.recipient.clone(), |id| Reliable(id, *message));
+                if let Err(error) = delivered {
+                    context.complete(context.sender(), error);
+                }
+                return;
+            },
+            Err(message) => message,
+        };
+        if let Ok(message) = Box::<Any>::downcast::<Confirm>(message) {
+            self.deliveries.confirm(message.0);
+        } else {
+            context.complete(context.sender(), self.deliveries.unconfirmed_count());
+        }
+    }
+}
+
+impl ReliableSender {
+    fn new((recipient, max_unconfirmed): (ActorRef, usize)) -> ReliableSender {
+        ReliableSender {
+            recipient: recipient,
+            deliveries: AtLeastOnceDelivery::new(Duration::from_millis(20), max_unconfirmed),
+        }
+    }
+}
+
+/// Ignores the first delivery of each message, confirms the next ones if `confirms`, and sends the
+/// strings it receives to the channel.
+struct Unreliable {
+    seen: Mutex<Vec<u64>>,
+    confirms: bool,
+    sender: Arc<Mutex<Sender<String>>>,
+}
+
+impl Actor for Unreliable {
+    fn receive(&self, message: Box<Any>, context: ActorCell) {
+        if let Ok(message) = Box::<Any>::downcast::<Reliable>(message) {
+            let mut seen = self.seen.lock().unwrap();
+            if !seen.contains(&message.0) {
+                seen.push(message.0);
+                return;
+            }
+            // The confirmation is queued before the string is seen on the channel.
+            if self.confirms {
+                context.tell(context.sender(), Confirm(message.0));
+            }
+            let _ = self.sender.lock().unwrap().send(message.1.clone());
+        }
+    }
+}
+
+impl Unreliable {
+    fn new((confirms, sender): (bool, Arc<Mutex<Sender<String>>>)) -> Unreliable {
+        Unreliable {
+            seen: Mutex::new(Vec::new()),
+            confirms: confirms,
+            sender: sender,
+        }
+    }
+}
+
+#[test]
+fn at_least_once_delivery() {
+    let actor_system = ActorSystem::new("test".to_owned());
+    let (tx, rx) = channel();
+    let tx = Arc::new(Mutex::new(tx));
+    let unconfirmed = |sender: &ActorRef| {
+        let count = actor_system.ask_anonymous(sender.clone(), ());
+        actor_system.extract_result::<usize>(count)
+    };
+
+    // The messages are delivered again until they are confirmed.
+    let recipient = actor_system.actor_of(Props::new(Arc::new(Unreliable::new), (true, tx.clone())),
+                                          "unreliable".to_owned()).unwrap();
+    let props = Props::new(Arc::new(ReliableSender::new), (recipient, 10));
+    let sender = actor_system.actor_of(props, "sender".to_owned()).unwrap();
+    actor_system.tell(sender.clone(), "hello".to_owned());
+    actor_system.tell(sender.clone(), "world".to_owned());
+    let mut received: Vec<String> = (0..2).map(|_| rx.recv_timeout(Duration::from_secs(1)).unwrap()).collect();
+    received.sort();
+    assert_eq!(vec!["hello".to_owned(), "world".to_owned()], received);
+    assert_eq!(0, unconfirmed(&sender));
+    // Confirmed messages are not delivered again.
+    assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+
+    // No message is sent while the maximum number of unconfirmed messages is reached.
+    let recipient = actor_system.actor_of(Props::new(Arc::new(Unreliable::new), (false, tx.clone())),
+                                          "forgetful".to_owned()).unwrap();
+    let props = Props::new(Arc::new(ReliableSender::new), (recipient, 2));
+    let sender = actor_system.actor_of(props, "limited".to_owned()).unwrap();
+    actor_system.tell(sender.clone(), "1".to_owned());
+    actor_system.tell(sender.clone(), "2".to_owned());
+    let refused = actor_system.ask_anonymous(sender.clone(), "3".to_owned());
+    assert_eq!(RobotsError::TooManyUnconfirmed(2), actor_system.extract_result::<RobotsError>(refused));
+    assert_eq!(2, unconfirmed(&sender));
+    let mut received: Vec<String> = (0..4).map(|_| rx.recv_timeout(Duration::from_secs(1)).unwrap()).collect();
+    received.sort();
+    assert_eq!(vec!["1".to_owned(), "1".to_owned(), "2".to_owned(), "2".to_owned()], received);
+
+    actor_system.shutdown();
+}
+
+/// Settings of a cluster node on a free local port, with short intervals.
+fn cluster_settings() -> ClusterSettings {
+    let mut settings = ClusterSettings::new("127.0.0.1:0".to_owned());
+    settings.gossip_interval = Duration::from_millis(20);
+    settings.heartbeat_interval = Duration::from_millis(20);
+    settings.acceptable_heartbeat_pause = Duration::from_millis(300);
+    settings
+}
+
+/// Waits until the condition holds, and panics after 5 seconds.
+fn wait_until<F: Fn() -> bool>(description: &str, condition: F) {
+    for _ in 0..500 {
+        if condition() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("Timed out waiting until {}", description);
+}
+
+/// Statuses of the members known by a node, by address.
+fn statuses(cluster: &Cluster) -> Vec<(String, MemberStatus)> {
+    cluster.members().into_iter().map(|member| (member.address, member.status)).collect()
+}
+
+#[test]
+fn cluster_membership() {
+    let systems: Vec<ActorSystem> = (0..3).map(|_| ActorSystem::new("test".to_owned())).collect();
+    let mut settings = cluster_settings();
+    settings.auto_down_unreachable_after = Some(Duration::from_millis(100));
+    let clusters: Vec<Cluster> = systems.iter().map(|system| Cluster::new(system, settings.clone()).unwrap()).collect();
+    let addresses: Vec<String> = clusters.iter().map(|cluster| cluster.address().to_owned()).collect();
+    let rx = listen(&systems[0], vec![TypeId::of::<ClusterEvent>()]);
+
+    // The first seed node starts the cluster, the others join it.
+    for cluster in clusters.iter() {
+        cluster.join(vec![addresses[0].clone()]);
+    }
+    let mut all_up: Vec<(String, MemberStatus)> = addresses.iter().map(|address| (address.clone(), MemberStatus::Up))
+                                                                    .collect();
+    all_up.sort();
+    for cluster in clusters.iter() {
+        wait_until("all the members are up", || statuses(cluster) == all_up);
+    }
+    let mut up_events: Vec<String> = addresses.iter().map(|address| format!("up {}", address)).collect();
+    while !up_events.is_empty() {
+        let event = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        up_events.retain(|expected| *expected != event);
+    }
+    assert_eq!(Some(all_up[0].0.clone()), clusters[2].leader());
+    assert!(clusters[0].self_member().is_older_than(&clusters[1].self_member()));
+    assert!(clusters[0].self_member().is_older_than(&clusters[2].self_member()));
+
+    // A member leaving is removed, and stops taking part in the cluster.
+    clusters[1].leave();
+    wait_for_event(&rx, &format!("removed {}", addresses[1]));
+    wait_until("the member is removed", || clusters[1].self_member().status == MemberStatus::Removed);
+    wait_until("the member is removed", || clusters[2].members().len() == 2);
+
+    // A crashed member is unreachable, and then downed and removed.
+    clusters[2].shutdown();
+    wait_for_event(&rx, &format!("unreachable {}", addresses[2]));
+    wait_for_event(&rx, &format!("removed {}", addresses[2]));
+    assert_eq!(vec![(addresses[0].clone(), MemberStatus::Up)], statuses(&clusters[0]));
+
+    for (cluster, system) in clusters.iter().zip(systems.iter()) {
+        cluster.shutdown();
+        system.shutdown();
+    }
+}
+
+#[test]
+fn oversized_cluster_message() {
+    let system = ActorSystem::new("test".to_owned());
+    let cluster = Cluster::new(&system, cluster_settings()).unwrap();
+
+    // A node announcing a message of 1GB is disconnected before anything is allocated.
+    let mut stream = TcpStream::connect(cluster.address()).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    stream.write_all(&(1u32 << 30).to_bytes()).unwrap();
+    let mut byte = [0; 1];
+    assert_eq!(0, stream.read(&mut byte).unwrap());
+
+    cluster.shutdown();
+    system.shutdown();
+}
+
+#[test]
+fn phi_accrual_failure_detector() {
+    let start = Instant::now();
+    let at = |millis: u64| start + Duration::from_millis(millis);
+    let failure_detector = |threshold| {
+        let mut failure_detector = PhiAccrualFailureDetector::new(threshold,
+                                                                  Duration::from_millis(100),
+                                                                  Duration::from_millis(100),
+                                                                  Duration::from_millis(0));
+        assert_eq!(0.0, failure_detector.phi_at(at(0)));
+        for i in 0..10 {
+            failure_detector.heartbeat_at(at(i * 100));
+        }
+        failure_detector
+    };
+
+    // The suspicion grows with the time since the last heartbeat.
+    let default = failure_detector(8.0);
+    let phis: Vec<f64> = [1000, 1200, 1400, 1600].iter().map(|millis| default.phi_at(at(*millis))).collect();
+    assert!(phis.windows(2).all(|phis| phis[0] < phis[1]));
+    assert!(default.is_available_at(at(1400)));
+    assert!(!default.is_available_at(at(1600)));
+
+    // A higher threshold suspects the node later.
+    let tolerant = failure_detector(16.0);
+    assert!(tolerant.is_available_at(at(1600)));
+    assert!(!tolerant.is_available_at(at(1900)));
+}
+
+/// Monitors the given distant actor, and sends the reasons of its failures to the channel. Answers
+/// to any message once it monitors.
+struct DistantMonitor {
+    distant: ActorRef,
+    sender: Arc<Mutex<Sender<RobotsError>>>,
+}
+
+impl Actor for DistantMonitor {
+    fn pre_start(&self, context: ActorCell) {
+        let sender = self.sender.clone();
+        context.monitor(self.distant.clone(), Arc::new(move |failure, _context| {
+            let _ = sender.lock().unwrap().send(failure.reason().clone());
+        }));
+    }
+
+    fn receive(&self, _message: Box<Any>, context: ActorCell) {
+        context.complete(context.sender(), ());
+    }
+}
+
+impl DistantMonitor {
+    fn new((distant, sender): (ActorRef, Arc<Mutex<Sender<RobotsError>>>)) -> DistantMonitor {
+        DistantMonitor {
+            distant: distant,
+            sender: sender,
+        }
+    }
+}
+
+#[test]
+fn monitor_distant_actors() {
+    let systems: Vec<ActorSystem> = (0..2).map(|_| ActorSystem::new("test".to_owned())).collect();
+    let mut settings = cluster_settings();
+    settings.auto_down_unreachable_after = Some(Duration::from_millis(100));
+    let clusters: Vec<Cluster> = systems.iter().map(|system| Cluster::new(system, settings.clone()).unwrap()).collect();
+    let addresses: Vec<String> = clusters.iter().map(|cluster| cluster.address().to_owned()).collect();
+    for cluster in clusters.iter() {
+        cluster.join(vec![addresses[0].clone()]);
+    }
+    wait_until("both members are up", || {
+        clusters[0].members().iter().filter(|member| member.status == MemberStatus::Up).count() == 2
+    });
+
+    let distant = ActorRef::new_distant(ActorPath::new_distant("/user/distant".to_owned(), addresses[1].clone()));
+    let (tx, rx) = channel();
+    let props = Props::new(Arc::new(DistantMonitor::new), (distant, Arc::new(Mutex::new(tx))));
+    let monitor = systems[0].actor_of(props, "monitor".to_owned()).unwrap();
+    let started = systems[0].ask_anonymous(monitor, ());
+    systems[0].extract_result_timeout::<()>(started, Duration::from_secs(5)).unwrap();
+    assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+    // The node of the distant actor goes silent, it is unreachable and then removed.
+    clusters[1].shutdown();
+    assert_eq!(RobotsError::Unreachable(addresses[1].clone()), rx.recv_timeout(Duration::from_secs(5)).unwrap());
+    assert_eq!(RobotsError::ActorStopped("/user/distant".to_owned()), rx.recv_timeout(Duration::from_secs(5)).unwrap());
+
+    for (cluster, system) in clusters.iter().zip(systems.iter()) {
+        cluster.shutdown();
+        system.shutdown();
+    }
+}
+
+/// Sends the index of its node and the Strings it receives to the channel, used as the entities
+/// of ShardRegions and as cluster singletons.
+struct NodeReporter {
+    node: usize,
+    sender: Arc<Mutex<Sender<(usize, String)>>>,
+}
+
+impl Actor for NodeReporter {
+    fn receive(&self, message: Box<Any>, _context: ActorCell) {
+        if let Ok(message) = Box::<Any>::downcast::<String>(message) {
+            let _ = self.sender.lock().unwrap().send((self.node, *message));
+        }
+    }
+}
+
+impl NodeReporter {
+    fn new((node, sender): (usize, Arc<Mutex<Sender<(usize, String)>>>)) -> NodeReporter {
+        NodeReporter {
+            node: node,
+            sender: sender,
+        }
+    }
+}
+
+/// Creates a ShardRegion of ShardedEntities on the node, where the messages are the ids of their
+/// entities.
+fn shard_region(system: &ActorSystem,
+                cluster: &Cluster,
+                node: usize,
+                name: &str,
+                settings: ShardingSettings,
+                tx: &Sender<(usize, String)>)
+                -> ActorRef {
+    let entity_props = Props::new(Arc::new(NodeReporter::new), (node, Arc::new(Mutex::new(tx.clone()))));
+    let props = ShardRegion::<String>::props(cluster.clone(), entity_props, settings, |entity_id: &String| entity_id.clone());
+    system.actor_of(props, name.to_owned()).unwrap()
+}
+
+/// Sends the ids of the entities to the ShardRegion.
+fn tell_entities(system: &ActorSystem, region: &ActorRef, entity_ids: &[String]) {
+    for entity_id in entity_ids.iter() {
+        system.tell(region.clone(), entity_id.clone());
+    }
+}
+
+#[test]
+fn cluster_sharding() {
+    let systems: Vec<ActorSystem> = (0..3).map(|_| ActorSystem::new("test".to_owned())).collect();
+    let clusters: Vec<Cluster> = systems.iter().map(|system| Cluster::new(system, cluster_settings()).unwrap()).collect();
+    let seed_nodes = vec![clusters[0].address().to_owned()];
+    let up = |n: usize| {
+        clusters.iter().take(n).all(|cluster| {
+            let members = cluster.members();
+            members.len() == n && members.iter().all(|member| member.status == MemberStatus::Up)
+        })
+    };
+    let entities = |region: &ActorRef, system: &ActorSystem| {
+        let entities = system.ask_anonymous(region.clone(), ShardRegionMessage::GetEntities);
+        let entities: Vec<String> = system.extract_result(entities);
+        entities
+    };
+    let entity_ids: Vec<String> = (0..50).map(|i| format!("entity-{}", i)).collect();
+    let mut sorted_ids = entity_ids.clone();
+    sorted_ids.sort();
+    let (tx, rx) = channel();
+    let settings = ShardingSettings::new(100);
+    let regions: Vec<ActorRef> = (0..3).map(|i| shard_region(&systems[i], &clusters[i], i, "entities", settings, &tx))
+                                       .collect();
+
+    // Messages sent before the cluster is up are kept until their shards have an owner.
+    tell_entities(&systems[0], &regions[0], &entity_ids);
+    clusters[0].join(seed_nodes.clone());
+    clusters[1].join(seed_nodes.clone());
+    let mut received: Vec<String> = (0..entity_ids.len()).map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap().1)
+                                                         .collect();
+    received.sort();
+    assert_eq!(sorted_ids, received);
+    wait_until("two members are up", || up(2));
+
+    // Each entity lives on one node, whichever region the messages go through.
+    tell_entities(&systems[0], &regions[0], &entity_ids);
+    tell_entities(&systems[1], &regions[1], &entity_ids);
+    let mut nodes: HashMap<String, Vec<usize>> = HashMap::new();
+    for _ in 0..2 * entity_ids.len() {
+        let (node, entity_id) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        nodes.entry(entity_id).or_insert_with(Vec::new).push(node);
+    }
+    assert_eq!(entity_ids.len(), nodes.len());
+    assert!(nodes.values().all(|nodes| nodes.len() == 2 && nodes[0] == nodes[1]));
+    wait_until("the entities are on their node", || {
+        let mut all_entities: Vec<String> = (0..2).flat_map(|i| entities(&regions[i], &systems[i])).collect();
+        all_entities.sort();
+        all_entities == sorted_ids
+    });
+
+    // Some shards move to a node joining the cluster, and their entities are created there.
+    clusters[2].join(seed_nodes.clone());
+    wait_until("three members are up", || up(3));
+    wait_until("the shards are rebalanced", || {
+        tell_entities(&systems[2], &regions[2], &entity_ids);
+        let mut all_entities: Vec<String> = (0..3).flat_map(|i| entities(&regions[i], &systems[i])).collect();
+        all_entities.sort();
+        all_entities == sorted_ids && !entities(&regions[2], &systems[2]).is_empty()
+    });
+
+    // Idle entities are stopped.
+    let mut settings = ShardingSettings::new(100);
+    settings.passivate_idle_after = Some(Duration::from_millis(300));
+    let regions: Vec<ActorRef> = (0..3).map(|i| shard_region(&systems[i], &clusters[i], i, "idle", settings, &tx))
+                                       .collect();
+    tell_entities(&systems[0], &regions[0], &entity_ids);
+    wait_until("the entities are created", || {
+        (0..3).map(|i| entities(&regions[i], &systems[i]).len()).sum::<usize>() == entity_ids.len()
+    });
+    wait_until("the idle entities are stopped", || (0..3).all(|i| entities(&regions[i], &systems[i]).is_empty()));
+
+    for (cluster, system) in clusters.iter().zip(systems.iter()) {
+        cluster.shutdown();
+        system.shutdown();
+    }
+}
+
+#[test]
+fn cluster_singleton() {
+    let systems: Vec<ActorSystem> = (0..3).map(|_| ActorSystem::new("test".to_owned())).collect();
+    let clusters: Vec<Cluster> = systems.iter().map(|system| Cluster::new(system, cluster_settings()).unwrap()).collect();
+    let (tx, rx) = channel();
+    let tx = Arc::new(Mutex::new(tx));
+    let proxies: Vec<ActorRef> = (0..3).map(|i| {
+        let singleton_props = Props::new(Arc::new(NodeReporter::new), (i, tx.clone()));
+        let props = ClusterSingletonManager::props(clusters[i].clone(), "singleton".to_owned(), singleton_props);
+        systems[i].actor_of(props, "manager".to_owned()).unwrap();
+        let props = ClusterSingletonProxy::<String>::props(clusters[i].clone(), "/user/manager/singleton".to_owned());
+        systems[i].actor_of(props, "proxy".to_owned()).unwrap()
+    }).collect();
+
+    // Messages sent before the singleton runs are kept by the proxy.
+    systems[1].tell(proxies[1].clone(), "first".to_owned());
+    clusters[0].join(vec![clusters[0].address().to_owned()]);
+    wait_until("the first member is up", || clusters[0].self_member().status == MemberStatus::Up);
+    for cluster in clusters.iter().skip(1) {
+        cluster.join(vec![clusters[0].address().to_owned()]);
+    }
+    assert_eq!((0, "first".to_owned()), rx.recv_timeout(Duration::from_secs(5)).unwrap());
+    wait_until("all the members are up", || {
+        clusters.iter().all(|cluster| cluster.members().iter().filter(|member| member.status == MemberStatus::Up).count() == 3)
+    });
+
+    // The singleton runs on the oldest node, whichever proxy the messages go through.
+    for (proxy, system) in proxies.iter().zip(systems.iter()) {
+        system.tell(proxy.clone(), "up".to_owned());
+    }
+    for _ in 0..3 {
+        assert_eq!((0, "up".to_owned()), rx.recv_timeout(Duration::from_secs(5)).unwrap());
+    }
+    assert!(systems[1].lookup("/user/manager/singleton").is_none());
+    assert!(systems[2].lookup("/user/manager/singleton").is_none());
+
+    // The singleton is handed over to the next oldest node when the oldest leaves.
+    let next = if clusters[1].self_member().is_older_than(&clusters[2].self_member()) { 1 } else { 2 };
+    clusters[0].leave();
+    wait_until("the oldest member is removed", || clusters[next].members().len() == 2);
+    wait_until("the singleton is stopped", || systems[0].lookup("/user/manager/singleton").is_none());
+    systems[3 - next].tell(proxies[3 - next].clone(), "handed over".to_owned());
+    assert_eq!((next, "handed over".to_owned()), rx.recv_timeout(Duration::from_secs(5)).unwrap());
+    assert!(systems[3 - next].lookup("/user/manager/singleton").is_none());
+
+    for (cluster, system) in clusters.iter().zip(systems.iter()) {
+        cluster.shutdown();
+        system.shutdown();
+    }
+}